@@ -0,0 +1,198 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum BackupError {
+    #[error("Could not read or write backup: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid backup entry on line {0}: `{1}`")]
+    Syntax(usize, String),
+}
+
+/// Tags removed from files, so they can be restored later.
+///
+/// Written as a section per file, starting with the file's path in square brackets and followed
+/// by one `KEY=VALUE` per line. Line breaks and backslashes in paths and values are escaped as
+/// `\n`, `\r`, and `\\`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Backup {
+    files: BTreeMap<PathBuf, BTreeMap<String, Vec<String>>>,
+}
+impl Backup {
+    pub fn from_path(path: &Path) -> Result<Self, BackupError> {
+        std::fs::read_to_string(path)?.parse()
+    }
+
+    /// Appends the backup to the file, creating it if it doesn't exist.
+    pub fn write(&self, path: &Path) -> Result<(), BackupError> {
+        use std::io::Write;
+
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        write!(file, "{self}")?;
+
+        Ok(())
+    }
+
+    /// Records tags removed from a file, if any were removed.
+    pub fn record(&mut self, path: &Path, removed: BTreeMap<String, Vec<String>>) {
+        if removed.is_empty() {
+            return;
+        }
+        let tags = self.files.entry(path.to_path_buf()).or_default();
+        removed
+            .into_iter()
+            .for_each(|(k, mut v)| tags.entry(k).or_default().append(&mut v));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// The files with backed up tags, and their tags.
+    pub fn files(&self) -> impl Iterator<Item = (&PathBuf, &BTreeMap<String, Vec<String>>)> {
+        self.files.iter()
+    }
+}
+impl Display for Backup {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (path, tags) in &self.files {
+            writeln!(f, "[{}]", escape(&path.to_string_lossy()))?;
+            for (key, values) in tags {
+                for value in values {
+                    writeln!(f, "{}={}", key, escape(value))?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+impl FromStr for Backup {
+    type Err = BackupError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut backup = Backup::default();
+        let mut tags = None;
+        for (i, line) in s.lines().enumerate() {
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(path) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                let path = PathBuf::from(unescape(path));
+                tags = Some(backup.files.entry(path).or_default());
+            } else {
+                let (key, value) = line
+                    .split_once('=')
+                    .filter(|(k, _)| !k.is_empty())
+                    .ok_or(BackupError::Syntax(i + 1, line.to_string()))?;
+                tags.as_mut()
+                    .ok_or(BackupError::Syntax(i + 1, line.to_string()))?
+                    .entry(key.to_string())
+                    .or_insert_with(Vec::new)
+                    .push(unescape(value));
+            }
+        }
+
+        Ok(backup)
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+fn unescape(s: &str) -> String {
+    let mut out = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => {
+                out.push('\n');
+                chars.next();
+            }
+            ('\\', Some('r')) => {
+                out.push('\r');
+                chars.next();
+            }
+            ('\\', Some('\\')) => {
+                out.push('\\');
+                chars.next();
+            }
+            (c, _) => out.push(c),
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_backup_round_trip() {
+        let mut backup = Backup::default();
+        let mut removed = BTreeMap::new();
+        removed.insert(
+            "REPLAYGAIN_TRACK_GAIN".to_string(),
+            vec!["-6.5 dB".to_string()],
+        );
+        removed.insert(
+            "UNSYNCED LYRICS".to_string(),
+            vec!["Line one\nLine \\two".to_string(), "a=b".to_string()],
+        );
+        backup.record(Path::new("Nujabes/01 Feather.flac"), removed);
+
+        let written = backup.to_string();
+        assert_eq!(
+            written,
+            "[Nujabes/01 Feather.flac]\n\
+             REPLAYGAIN_TRACK_GAIN=-6.5 dB\n\
+             UNSYNCED LYRICS=Line one\\nLine \\\\two\n\
+             UNSYNCED LYRICS=a=b\n"
+        );
+        assert_eq!(Backup::from_str(&written).unwrap(), backup);
+
+        backup.record(Path::new("Nujabes/02 Aruarian Dance.flac"), BTreeMap::new());
+        assert_eq!(backup.files().count(), 1);
+        assert!(Backup::default().is_empty());
+    }
+
+    #[test]
+    fn test_backup_crlf() {
+        let mut backup = Backup::default();
+        let mut removed = BTreeMap::new();
+        removed.insert(
+            "LYRICS".to_string(),
+            vec!["Line one\r\nLine two\r".to_string()],
+        );
+        backup.record(Path::new("Nujabes/01 Feather.flac"), removed);
+
+        let written = backup.to_string();
+        assert_eq!(
+            written,
+            "[Nujabes/01 Feather.flac]\nLYRICS=Line one\\r\\nLine two\\r\n"
+        );
+        assert_eq!(Backup::from_str(&written).unwrap(), backup);
+    }
+
+    #[test]
+    fn test_backup_errors() {
+        assert!(matches!(
+            Backup::from_str("KEY=VALUE"),
+            Err(BackupError::Syntax(1, _))
+        ));
+        assert!(matches!(
+            Backup::from_str("[a.flac]\nKEY"),
+            Err(BackupError::Syntax(2, _))
+        ));
+    }
+}
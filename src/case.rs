@@ -0,0 +1,351 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::collections::HashMap;
+use std::str::FromStr;
+use thiserror::Error;
+
+/// Words written the same way whatever the case, e.g. `feat.` is never capitalized and `DJ` is
+/// always uppercase.
+pub const CASE_EXCEPTIONS: &[&str] = &[
+    "feat.", "ft.", "vs.", "DJ", "MC", "AC/DC", "UK", "USA", "TV",
+];
+
+lazy_static! {
+    /// Roman numerals from 1 to 39, e.g. `II` or `XIV`
+    static ref NUMERAL: Regex = Regex::new(r"(?i)^X{0,3}(?:IX|IV|V?I{0,3})$").unwrap();
+    /// Initialisms written with dots, e.g. `R.E.M` (without its trailing dot)
+    static ref INITIALISM: Regex = Regex::new(r"^(?:\p{Lu}\.)+\p{Lu}$").unwrap();
+}
+
+#[derive(Error, Debug)]
+pub enum CaseError {
+    #[error("Unknown case `{0}`, expected one of title, sentence, upper, or lower")]
+    Case(String),
+    #[error("Unknown language `{0}`, expected one of en, de, es, fr, it, nl, or pt")]
+    Language(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaseStyle {
+    /// Capitalizes every word, except small words such as `the` within a value.
+    Title,
+    /// Capitalizes the first word of each sentence.
+    Sentence,
+    Upper,
+    Lower,
+}
+impl FromStr for CaseStyle {
+    type Err = CaseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.to_lowercase().as_str() {
+            "title" => Self::Title,
+            "sentence" => Self::Sentence,
+            "upper" => Self::Upper,
+            "lower" => Self::Lower,
+            _ => Err(CaseError::Case(s.to_string()))?,
+        })
+    }
+}
+
+/// Language whose small words (articles, conjunctions, and short prepositions) are left in
+/// lowercase by title case.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    English,
+    German,
+    Spanish,
+    French,
+    Italian,
+    Dutch,
+    Portuguese,
+}
+impl Language {
+    fn small_words(&self) -> &'static [&'static str] {
+        match self {
+            Self::English => &[
+                "a", "an", "and", "as", "at", "but", "by", "for", "from", "in", "into", "nor",
+                "of", "off", "on", "onto", "or", "over", "per", "so", "the", "to", "up", "via",
+                "vs", "with", "yet",
+            ],
+            Self::German => &[
+                "am", "an", "auf", "aus", "bei", "das", "dem", "den", "der", "des", "die", "ein",
+                "eine", "einem", "einen", "einer", "im", "in", "mit", "und", "von", "vom", "zu",
+                "zum", "zur",
+            ],
+            Self::Spanish => &[
+                "a", "al", "con", "de", "del", "el", "en", "la", "las", "los", "o", "para", "por",
+                "un", "una", "y",
+            ],
+            Self::French => &[
+                "à", "au", "aux", "de", "des", "du", "en", "et", "la", "le", "les", "ou", "par",
+                "pour", "sur", "un", "une",
+            ],
+            Self::Italian => &[
+                "a", "al", "con", "da", "dei", "del", "della", "di", "e", "i", "il", "in", "la",
+                "le", "lo", "per", "un", "una",
+            ],
+            Self::Dutch => &[
+                "de", "den", "der", "een", "en", "het", "in", "met", "of", "op", "te", "van",
+                "voor",
+            ],
+            Self::Portuguese => &[
+                "a", "as", "com", "da", "das", "de", "do", "dos", "e", "em", "na", "no", "o", "os",
+                "para", "por", "um", "uma",
+            ],
+        }
+    }
+}
+impl FromStr for Language {
+    type Err = CaseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.to_lowercase().as_str() {
+            "en" | "english" => Self::English,
+            "de" | "german" => Self::German,
+            "es" | "spanish" => Self::Spanish,
+            "fr" | "french" => Self::French,
+            "it" | "italian" => Self::Italian,
+            "nl" | "dutch" => Self::Dutch,
+            "pt" | "portuguese" => Self::Portuguese,
+            _ => Err(CaseError::Language(s.to_string()))?,
+        })
+    }
+}
+
+/// Changes the case of values, writing exceptions (matched ignoring case and surrounding
+/// punctuation) as given.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaseTransform {
+    style: CaseStyle,
+    language: Language,
+    exceptions: HashMap<String, String>,
+}
+impl CaseTransform {
+    pub fn new(style: CaseStyle, language: Language, exceptions: &[impl AsRef<str>]) -> Self {
+        let exceptions = exceptions
+            .iter()
+            .map(|e| affixes(e.as_ref()).1)
+            .filter(|e| !e.is_empty())
+            .map(|e| (e.to_lowercase(), e.to_string()))
+            .collect();
+
+        CaseTransform {
+            style,
+            language,
+            exceptions,
+        }
+    }
+
+    pub fn apply(&self, value: &str) -> String {
+        let style = match self.style {
+            CaseStyle::Upper => return value.to_uppercase(),
+            CaseStyle::Lower => return value.to_lowercase(),
+            style => style,
+        };
+
+        let tokens = value
+            .split_inclusive(char::is_whitespace)
+            .collect::<Vec<_>>();
+        let words = tokens
+            .iter()
+            .enumerate()
+            .filter(|(_, t)| !affixes(t.trim_end()).1.is_empty())
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+
+        let mut out = String::new();
+        let mut phrase_start = true;
+        for (i, token) in tokens.iter().enumerate() {
+            let word = token.trim_end();
+            let space = &token[word.len()..];
+            let (prefix, core, suffix) = affixes(word);
+
+            let starts_phrase = phrase_start || prefix.contains(['(', '[', '{', '"', '“']);
+            let is_last = words.last() == Some(&i);
+            let core = match self.exceptions.get(&core.to_lowercase()) {
+                Some(exception) => exception.to_owned(),
+                None if style == CaseStyle::Title
+                    && !starts_phrase
+                    && !is_last
+                    && self.is_small_word(core) =>
+                {
+                    core.to_lowercase()
+                }
+                None if is_numeral(core) => core.to_uppercase(),
+                None if self.language == Language::English && is_pronoun(core) => capitalize(core),
+                None if style == CaseStyle::Sentence && !starts_phrase => lowercase(core),
+                None => capitalize(core),
+            };
+            out.extend([prefix, core.as_str(), suffix, space]);
+
+            if !word.is_empty() {
+                let ends = match style {
+                    CaseStyle::Sentence => &['.', '!', '?', ':'][..],
+                    _ => &[':', '.', '!', '?', '(', '-', '–', '—'][..],
+                };
+                phrase_start = word.ends_with(ends) && !self.is_abbreviation(word);
+            }
+        }
+
+        out
+    }
+
+    /// Whether a word ending with `.` is an exception such as `feat.` or an initialism such as
+    /// `R.E.M.`, rather than ending a sentence.
+    fn is_abbreviation(&self, word: &str) -> bool {
+        let core = affixes(word).1;
+        word.ends_with('.')
+            && (self.exceptions.contains_key(&core.to_lowercase()) || INITIALISM.is_match(core))
+    }
+
+    fn is_small_word(&self, word: &str) -> bool {
+        self.language
+            .small_words()
+            .contains(&word.to_lowercase().as_str())
+    }
+}
+
+/// Splits a word into its leading punctuation, the word itself, and its trailing punctuation.
+fn affixes(word: &str) -> (&str, &str, &str) {
+    let core = word.trim_start_matches(|c: char| !c.is_alphanumeric());
+    let prefix = &word[..word.len() - core.len()];
+    let trimmed = core.trim_end_matches(|c: char| !c.is_alphanumeric());
+    (prefix, trimmed, &core[trimmed.len()..])
+}
+
+/// Whether a word is a Roman numeral of at least two letters, e.g. `II` (as `I` and `V` are also
+/// words).
+fn is_numeral(word: &str) -> bool {
+    word.len() > 1 && NUMERAL.is_match(word)
+}
+
+/// Whether a word is the English pronoun `I`, including its contractions such as `I'm`.
+fn is_pronoun(word: &str) -> bool {
+    let word = word.to_lowercase();
+    word == "i" || word.starts_with("i'") || word.starts_with("i\u{2019}")
+}
+
+/// Whether a word is written in a case that should be kept, as it has capitals after its first
+/// letter without being all capitals (e.g. `McCartney`), or is an initialism (e.g. `R.E.M`).
+fn is_styled(word: &str) -> bool {
+    let inner_capitals = word.chars().skip(1).any(char::is_uppercase);
+    (inner_capitals && word.chars().any(char::is_lowercase)) || INITIALISM.is_match(word)
+}
+
+/// Lowercases a word, unless its case should be kept (see [`is_styled`]).
+fn lowercase(word: &str) -> String {
+    match is_styled(word) {
+        true => word.to_string(),
+        false => word.to_lowercase(),
+    }
+}
+
+/// Uppercases the first letter of each hyphenated part of a word. The rest of a part is kept as
+/// written, unless the part is all capitals (e.g. `ROCK`), where it is lowercased.
+fn capitalize(word: &str) -> String {
+    word.split('-')
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => {
+                    let rest = match is_styled(part) || part.chars().any(char::is_lowercase) {
+                        true => chars.as_str().to_string(),
+                        false => chars.as_str().to_lowercase(),
+                    };
+                    first.to_uppercase().chain(rest.chars()).collect()
+                }
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn title(value: &str) -> String {
+        CaseTransform::new(CaseStyle::Title, Language::English, CASE_EXCEPTIONS).apply(value)
+    }
+
+    #[test]
+    fn test_title_case() {
+        assert_eq!(
+            title("the man with the iron fist"),
+            "The Man with the Iron Fist"
+        );
+        assert_eq!(
+            title("ROCK AND ROLL AIN'T NOISE POLLUTION"),
+            "Rock and Roll Ain't Noise Pollution"
+        );
+        assert_eq!(
+            title("battle born (live at the o2)"),
+            "Battle Born (Live at the O2)"
+        );
+        assert_eq!(
+            title("spiral-shaped: a song of"),
+            "Spiral-Shaped: A Song Of"
+        );
+        assert_eq!(
+            title("luv (sic) part 3 Feat. shing02"),
+            "Luv (Sic) Part 3 feat. Shing02"
+        );
+        assert_eq!(
+            title("dj shadow vs. ac/dc - the last  word"),
+            "DJ Shadow vs. AC/DC - The Last  Word"
+        );
+        assert_eq!(title("rocky ii"), "Rocky II");
+        assert_eq!(title("final fantasy XIII"), "Final Fantasy XIII");
+        assert_eq!(
+            title("R.E.M. and OutKast meet Paul McCartney"),
+            "R.E.M. and OutKast Meet Paul McCartney"
+        );
+        assert_eq!(title("i think i'm ok"), "I Think I'm Ok");
+    }
+
+    #[test]
+    fn test_other_cases() {
+        let case =
+            |style, language, value| CaseTransform::new(style, language, &["DJ"]).apply(value);
+        assert_eq!(
+            case(CaseStyle::Title, Language::French, "la vie en rose"),
+            "La Vie en Rose"
+        );
+        assert_eq!(
+            case(
+                CaseStyle::Sentence,
+                Language::English,
+                "WHAT A DAY. dj set! ok"
+            ),
+            "What a day. DJ set! Ok"
+        );
+        assert_eq!(
+            case(
+                CaseStyle::Sentence,
+                Language::English,
+                "Then I Heard R.E.M. And McCartney In Part XIII"
+            ),
+            "Then I heard R.E.M. and McCartney in part XIII"
+        );
+        assert_eq!(
+            case(CaseStyle::Title, Language::Italian, "tutti i giorni"),
+            "Tutti i Giorni"
+        );
+        assert_eq!(
+            case(CaseStyle::Upper, Language::English, "Feather"),
+            "FEATHER"
+        );
+        assert_eq!(
+            case(CaseStyle::Lower, Language::English, "DJ Krush"),
+            "dj krush"
+        );
+        assert!(matches!(
+            CaseStyle::from_str("camel"),
+            Err(CaseError::Case(_))
+        ));
+        assert!(matches!(
+            Language::from_str("xx"),
+            Err(CaseError::Language(_))
+        ));
+    }
+}
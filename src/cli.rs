@@ -15,21 +15,63 @@ pub struct CliArgs {
 
     /// Clean duplicated fields, where both the tag field and value match.
     ///
-    /// Runs after all edits (if used alongside edits).
-    #[clap(long, short, conflicts_with_all = &["clean_all"], action)]
+    /// Values are normalized first, trimming and collapsing spaces, removing zero-width and control
+    /// characters, and unifying Unicode forms, quotes, and dashes. Runs after all edits (if used
+    /// alongside edits).
+    #[clap(long, short, conflicts_with_all = &["clean_all"], group = "cleaning", action)]
     pub clean: bool,
 
-    /// [Caution] Clean duplicated fields, and deletes ALL non-standard header comments.
+    /// [Caution] Clean duplicated fields as `--clean` does, and deletes ALL non-standard comments.
     ///
-    /// Runs after all edits (if used alongside edits). If the previous tags are valueable
-    /// do not run this until they have been back up, or ported to standard tags.
-    #[clap(long, conflicts_with_all = &["clean"], action)]
+    /// Values are normalized and deduplicated exactly as with `--clean` (including `--fold-case`)
+    /// before non-standard tags are deleted. Runs after all edits (if used alongside edits). If the
+    /// previous tags are valueable do not run this until they have been back up, or ported to
+    /// standard tags.
+    #[clap(long, conflicts_with_all = &["clean"], group = "cleaning", action)]
     pub clean_all: bool,
 
+    /// Treats values that only differ by case as duplicates when cleaning, keeping the first.
+    #[clap(long, requires = "cleaning", action)]
+    pub fold_case: bool,
+
+    /// Keeps non-standard tags matching a pattern when using `--clean-all`. Can be repeated
+    ///
+    /// Patterns are case-insensitive, where `*` matches any characters and `?` matches a single
+    /// character (e.g. `REPLAYGAIN_*`).
+    #[clap(long, requires = "clean_all", value_name = "PATTERN")]
+    pub keep: Vec<String>,
+
+    /// Appends the tags removed by `--clean-all` to a backup file, for use with `--restore`.
+    ///
+    /// The tags removed from each file are also printed to standard error.
+    #[clap(long, requires = "clean_all", value_name = "FILE", value_hint=clap::ValueHint::FilePath)]
+    pub backup: Option<String>,
+
+    /// Restores the tags in a backup file (written by `--backup`) to their files.
+    ///
+    /// Files in the backup are edited alongside any matching files, with their backed up values
+    /// appended before other edits.
+    #[clap(long, value_name = "FILE", value_hint=clap::ValueHint::FilePath)]
+    pub restore: Option<String>,
+
+    /// Migrates legacy tags written by other taggers to their standard name (e.g. YEAR to DATE).
+    ///
+    /// Values are merged into the standard tag, keeping any existing values. Runs before all other
+    /// edits, so can be safely used alongside `--clean-all`.
+    #[clap(long, action)]
+    pub migrate: bool,
+
     /// [Default] Set field to new given values. Previous values are deleted.
     #[clap(long, conflicts_with_all = &["append", "delete"], action, default_value_t = true)]
     pub set: bool,
 
+    /// Renders set and appended values as templates, e.g. `--title '{title} (Live)'`
+    ///
+    /// Variables are standard tags, `filename`, or `dirname`, read from each file's tags before any
+    /// edits. Without this, values are written as given.
+    #[clap(long, action)]
+    pub template: bool,
+
     /// Append flag. If set appends values leaving existing values untouched.
     #[clap(long, conflicts_with_all = &["set", "delete"], action)]
     pub append: bool,
@@ -49,9 +91,97 @@ pub struct CliArgs {
     pub list: bool,
 
     /// Performs the modification, ready for previewing, without saving/commiting the change.
+    ///
+    /// Unless listing, the tags changed in each file are printed.
     #[clap(long, action)]
     pub dry_run: bool,
 
+    /// Disables type coercion in queries.
+    ///
+    /// By default string tags are compared as integers or dates when compared against integer or
+    /// date literals (ignoring whitespace, leading zeros, and totals such as `3/12`), and dates are
+    /// compared at the less precise of their precisions. In strict mode tags are only compared as
+    /// the type they are stored as, and dates as given.
+    #[clap(long, action)]
+    pub strict: bool,
+
+    /// File declaring the types of tags, used when querying and to validate edits.
+    ///
+    /// Each line declares a tag as `TAG = type`, where type is one of `string`, `integer`
+    /// (optionally followed by an inclusive range, e.g. `integer 0..100`), `number`, `date`, or
+    /// `bool`. Lines starting with `#` are ignored.
+    #[clap(long, value_hint=clap::ValueHint::FilePath)]
+    pub schema: Option<String>,
+
+    /// Sets tags to the result of expressions, e.g. `Title = trim(Title); Album = upper(Album) if
+    /// Genre == "Classical"`. Can be repeated
+    ///
+    /// Statements are separated by `;` and applied in order, each as `TAG = EXPRESSION`, optionally
+    /// followed by `if CONDITION`. Expressions use the query language, along with the functions
+    /// `trim`, `upper`, and `lower`. Statements referring to a tag that isn't set are skipped.
+    /// Applied after all other edits.
+    #[clap(long, value_name = "STATEMENTS")]
+    pub eval: Vec<String>,
+
+    /// Applies the rules of a TOML file, each a query and the edits made to the files it matches.
+    ///
+    /// Each `[[rule]]` table has a `when` query, and any of `set` and `append` (tables of tags to
+    /// values), `delete` (a list of tags), and `eval` (statements as with `--eval`), along with an
+    /// optional `name`. Rules are applied in order after all other edits, with each rule's query
+    /// seeing the edits of earlier rules. Applies to every file in the current directory
+    /// (recursively), unless files or a query are given.
+    #[clap(long, value_name = "FILE", value_hint=clap::ValueHint::FilePath)]
+    pub rules: Option<String>,
+
+    /// Moves each file to a path built from its tags, e.g. `{albumartist}/{album}/{title}.flac`
+    ///
+    /// Uses the same variables as templates in tag values, where characters that can't be used in
+    /// file names are replaced with `_`. Files sharing a file's name (such as a `.cue`) are moved
+    /// with it, and album files (such as `cover.jpg`) are moved once every file in their directory
+    /// has moved to the same directory. Files are renamed after their tags are saved, and nothing
+    /// is moved if any two files would collide or a file would be overwritten.
+    #[clap(long, value_name = "TEMPLATE")]
+    pub rename: Option<String>,
+
+    /// Numbers the matching files of each directory from 1, setting TRACKNUMBER.
+    ///
+    /// Files are numbered in natural order of their file names (so `2.flac` comes before
+    /// `10.flac`), or of a tag given by `--number-by`. Applied after all other edits.
+    #[clap(long, action)]
+    pub auto_number: bool,
+
+    /// Orders files by a tag rather than their file name when using `--auto-number`.
+    #[clap(long, requires = "auto_number", value_name = "KEY")]
+    pub number_by: Option<String>,
+
+    /// Also sets TRACKTOTAL (and DISCTOTAL with `--number-discs`) when using `--auto-number`.
+    #[clap(long, requires = "auto_number", action)]
+    pub number_total: bool,
+
+    /// Also sets DISCNUMBER when using `--auto-number`, numbering the discs of each album in order.
+    ///
+    /// Directories sharing a parent directory are discs of an album when their files share an
+    /// ALBUM (and ALBUMARTIST), or their names look like discs (e.g. `CD1` and `CD2`).
+    #[clap(long, requires = "auto_number", action)]
+    pub number_discs: bool,
+
+    /// Sets a tag of each file from a line of a file, e.g. a tracklist. Can be repeated
+    ///
+    /// The first line is given to the first file, and so on, where files are in natural order of
+    /// their directory and then file name. Blank lines are skipped, and lengths (e.g. `(3:45)`)
+    /// are removed, along with track positions (e.g. `1.` or `A1`) if every line has one. Applied
+    /// after all other edits.
+    #[clap(long, num_args(2), value_names(["KEY", "FILE"]))]
+    pub values_from: Vec<String>,
+
+    /// Sets the title of each file from a line of a tracklist, as with `--values-from TITLE`.
+    #[clap(long, value_name = "FILE", value_hint=clap::ValueHint::FilePath)]
+    pub title_from_file: Option<String>,
+
+    /// Reads lines of `--title-from-file` given as `ARTIST - TITLE` as the artist and title
+    #[clap(long, requires = "title_from_file", action)]
+    pub with_artist: bool,
+
     #[command(flatten)]
     pub fields: Fields,
 
@@ -150,4 +280,241 @@ pub struct Fields {
     /// See the ISRC intro page for more information on ISRC numbers.
     #[clap(long, num_args(0..))]
     pub isrc: Option<Vec<String>>,
+
+    /// The artist(s) credited for the whole collection (e.g. 'Various Artists')
+    #[clap(long, num_args(0..))]
+    pub albumartist: Option<Vec<String>>,
+
+    /// The composer(s) of the work
+    #[clap(long, num_args(0..))]
+    pub composer: Option<Vec<String>>,
+
+    /// The conductor(s) of the performance
+    #[clap(long, num_args(0..))]
+    pub conductor: Option<Vec<String>>,
+
+    /// The disc number of this piece, if the collection spans multiple discs
+    #[clap(long, num_args(0..))]
+    pub discnumber: Option<Vec<String>>,
+
+    /// The total number of discs in the collection
+    #[clap(long, num_args(0..))]
+    pub disctotal: Option<Vec<String>>,
+
+    /// The total number of tracks in the collection (or disc)
+    #[clap(long, num_args(0..))]
+    pub tracktotal: Option<Vec<String>>,
+
+    /// Date the work was originally released, if this is a reissue
+    #[clap(long, num_args(0..))]
+    pub originaldate: Option<Vec<String>>,
+
+    /// The record label that released the collection
+    #[clap(long, num_args(0..))]
+    pub label: Option<Vec<String>>,
+
+    /// The label's catalog number for the release
+    #[clap(long, num_args(0..))]
+    pub catalognumber: Option<Vec<String>>,
+
+    /// The barcode (UPC/EAN) of the release
+    #[clap(long, num_args(0..))]
+    pub barcode: Option<Vec<String>>,
+
+    /// MusicBrainz recording ID
+    #[clap(long = "musicbrainz-trackid", num_args(0..))]
+    pub musicbrainz_trackid: Option<Vec<String>>,
+
+    /// MusicBrainz track ID (the recording's position on a release)
+    #[clap(long = "musicbrainz-releasetrackid", num_args(0..))]
+    pub musicbrainz_releasetrackid: Option<Vec<String>>,
+
+    /// MusicBrainz release ID
+    #[clap(long = "musicbrainz-albumid", num_args(0..))]
+    pub musicbrainz_albumid: Option<Vec<String>>,
+
+    /// MusicBrainz artist ID(s)
+    #[clap(long = "musicbrainz-artistid", num_args(0..))]
+    pub musicbrainz_artistid: Option<Vec<String>>,
+
+    /// MusicBrainz release artist ID(s)
+    #[clap(long = "musicbrainz-albumartistid", num_args(0..))]
+    pub musicbrainz_albumartistid: Option<Vec<String>>,
+
+    /// MusicBrainz release group ID
+    #[clap(long = "musicbrainz-releasegroupid", num_args(0..))]
+    pub musicbrainz_releasegroupid: Option<Vec<String>>,
+
+    /// MusicBrainz work ID
+    #[clap(long = "musicbrainz-workid", num_args(0..))]
+    pub musicbrainz_workid: Option<Vec<String>>,
+
+    /// MusicBrainz disc ID
+    #[clap(long = "musicbrainz-discid", num_args(0..))]
+    pub musicbrainz_discid: Option<Vec<String>>,
+
+    /// The lyrics of the track
+    #[clap(long, num_args(0..))]
+    pub lyrics: Option<Vec<String>>,
+
+    /// A free-form comment
+    #[clap(long, num_args(0..))]
+    pub comment: Option<Vec<String>>,
+
+    /// Any other tag, given as `KEY=VALUE`. Can be repeated
+    ///
+    /// Repeating a key gives it multiple values. Keys are case-insensitive, and may contain any
+    /// printable ASCII character other than `=`. When deleting, the key can be given alone.
+    #[clap(long = "tag", short = 'T', value_name = "KEY=VALUE")]
+    pub tags: Vec<String>,
+
+    /// Sets a tag to the given value, regardless of `--append`/`--delete`. Can be repeated
+    ///
+    /// Allows fields to be edited in different ways in a single pass. Per-field edits are applied
+    /// after all other edits, with deletes applied first, then sets, then appends.
+    #[clap(long, value_name = "KEY=VALUE")]
+    pub set_tag: Vec<String>,
+
+    /// Appends a value to a tag, regardless of `--set`/`--delete`. Can be repeated
+    #[clap(long, value_name = "KEY=VALUE")]
+    pub append_tag: Vec<String>,
+
+    /// Deletes a tag, regardless of `--set`/`--append`. Can be repeated
+    #[clap(long, value_name = "KEY")]
+    pub delete_tag: Vec<String>,
+
+    /// Copies the values of one tag to another, given as `SRC:DST`. Can be repeated
+    ///
+    /// The destination's values are replaced, or appended to with `--copy-append`. Copies, and
+    /// then moves, are applied after per-field edits. Files without the source tag are left
+    /// unchanged. Only the last `:` separates the tags, so the source may contain `:`.
+    #[clap(long, value_name = "SRC:DST")]
+    pub copy: Vec<String>,
+
+    /// Moves the values of one tag to another, given as `SRC:DST`, deleting the source. Can be
+    /// repeated
+    ///
+    /// The destination's values are replaced, or appended to with `--copy-append`.
+    #[clap(long = "move", value_name = "SRC:DST")]
+    pub move_tag: Vec<String>,
+
+    /// Appends the values copied or moved by `--copy` and `--move` to the destination's values,
+    /// rather than replacing them
+    #[clap(long, action)]
+    pub copy_append: bool,
+
+    /// Renames a tag, merging its values into any existing values of the new tag. Can be repeated
+    ///
+    /// Renames are applied before all other edits, after `--migrate`.
+    #[clap(long, num_args(2), value_names(["OLD", "NEW"]))]
+    pub rename_field: Vec<String>,
+
+    /// Reads tags from each file's path, e.g. `%artist%/%date% - %album%/%tracknumber% %title%`
+    ///
+    /// Each `%tag%` matches part of a directory or file name, and `%%` is a literal percent sign.
+    /// The pattern is matched against the end of the path, and the extension can be left out.
+    /// The tags are set (or appended with `--append`) before all other edits, except renames.
+    /// Files whose path doesn't match are left unchanged.
+    #[clap(long, value_name = "PATTERN")]
+    pub from_path: Option<String>,
+
+    /// Copies the tags of a reference file to each file, e.g. after fixing one track of an album
+    ///
+    /// Every tag of the reference is copied, or only those given by `--fields`, replacing each
+    /// tag's values (or appending with `--append`). Tags the reference doesn't have are left
+    /// unchanged. Copied before all other edits, except renames and tags read from paths.
+    #[clap(long, value_name = "FILE", value_hint=clap::ValueHint::FilePath)]
+    pub tags_from: Option<String>,
+
+    /// Only copies these tags when using `--tags-from`, e.g. `album,date,genre`
+    #[clap(
+        long = "fields",
+        requires = "tags_from",
+        value_delimiter = ',',
+        value_name = "KEYS"
+    )]
+    pub from_fields: Vec<String>,
+
+    /// Also replaces each file's pictures with the pictures of the `--tags-from` reference
+    #[clap(long, requires = "tags_from", action)]
+    pub with_pictures: bool,
+
+    #[command(flatten)]
+    pub values: Values,
+}
+
+#[derive(Debug, Hash, PartialEq, Eq, Args, Clone)]
+#[clap(next_help_heading = "Value Operations")]
+pub struct Values {
+    /// Replaces values of a tag with a sed style expression, `s/PATTERN/REPLACEMENT/FLAGS`
+    ///
+    /// Any character can be used as the delimiter in place of `/`. The replacement can refer to
+    /// capture groups with `\1`..`\9`, and the whole match with `&`. Flags are `g` to replace every
+    /// match in a value (rather than the first) and `i` to ignore case. A key of `*` applies to
+    /// every tag. Can be repeated
+    #[clap(long, num_args(2), value_names(["KEY", "EXPRESSION"]))]
+    pub replace: Vec<String>,
+
+    /// Changes the case of values of a tag, to one of title, sentence, upper, or lower. Can be
+    /// repeated
+    ///
+    /// Title case capitalizes every word except small words (such as `the` or `of`) within a
+    /// value, and sentence case only capitalizes the first word of each sentence. Exceptions such
+    /// as `feat.`, `DJ`, and `AC/DC` keep their case.
+    #[clap(long, num_args(2), value_names(["KEY", "CASE"]))]
+    pub case: Vec<String>,
+
+    /// Adds a word that keeps its case when using `--case`, e.g. `iPhone`. Can be repeated
+    #[clap(long, requires = "case", value_name = "WORD")]
+    pub case_exception: Vec<String>,
+
+    /// Language of the small words left in lowercase by title case, one of en (the default), de,
+    /// es, fr, it, nl, or pt.
+    #[clap(long, requires = "case", value_name = "LANG", default_value = "en")]
+    pub case_language: String,
+
+    /// Splits values of a tag into several values at delimiters, e.g. `A; B` into `A` and `B`. Can
+    /// be repeated
+    ///
+    /// The delimiters are `;`, ` / `, ` feat. `, and ` ft. ` (ignoring case), unless given by
+    /// `--split-delimiter`. Values are trimmed, and empty values removed.
+    #[clap(long, value_name = "KEY")]
+    pub split: Vec<String>,
+
+    /// Splits values at this delimiter when using `--split`, in place of the default delimiters.
+    /// Can be repeated
+    #[clap(long, requires = "split", value_name = "DELIMITER")]
+    pub split_delimiter: Vec<String>,
+
+    /// Removes a single value from a tag, leaving its other values. Can be repeated
+    ///
+    /// Value operations are applied after all other edits, in the order; replace, case, split,
+    /// remove, insert, dedupe, sort, max, then join.
+    #[clap(long, value_name = "KEY=VALUE")]
+    pub remove_value: Vec<String>,
+
+    /// Inserts a value at a position (starting from 1) of a tag. Can be repeated
+    ///
+    /// If the tag has fewer values than the position, the value is appended.
+    #[clap(long, value_name = "KEY:POSITION=VALUE")]
+    pub insert_value: Vec<String>,
+
+    /// Removes values of a tag that only differ by case, keeping the first. Can be repeated
+    #[clap(long, value_name = "KEY")]
+    pub dedupe_values: Vec<String>,
+
+    /// Sorts the values of a tag alphabetically, ignoring case. Can be repeated
+    #[clap(long, value_name = "KEY")]
+    pub sort_values: Vec<String>,
+
+    /// Caps the number of values of a tag, keeping the first values. Can be repeated
+    #[clap(long, value_name = "KEY=MAX")]
+    pub max_values: Vec<String>,
+
+    /// Joins the values of a tag into one value, separated by SEPARATOR, e.g. `artist=; `. Can be
+    /// repeated
+    ///
+    /// For players that only show the first value of a tag.
+    #[clap(long, value_name = "KEY=SEPARATOR")]
+    pub join: Vec<String>,
 }
@@ -9,9 +9,9 @@ use itertools::Itertools;
 use metaflac::block::VorbisComment;
 use metaflac::Tag;
 use pest::pratt_parser::PrattParser;
-use query::{build, Expr, QueryParser, Value};
+use query::{build, Assignments, EvalOptions, Expr, Metadata, Query, QueryParser, Value};
 use query::{QueryEvalError, QueryParseError, Rule};
-use std::collections::{HashSet, VecDeque};
+use std::collections::{BTreeMap, HashSet, VecDeque};
 use std::error::Error;
 use std::fs::ReadDir;
 use std::option::IntoIter;
@@ -20,15 +20,32 @@ use std::str::FromStr;
 use std::{clone, fs};
 
 // modules
+mod backup;
+mod case;
 mod cli;
+mod numbering;
 mod operations;
 mod query;
+mod rename;
+mod replace;
+mod rules;
+mod schema;
 mod tags;
+mod template;
+mod tracklist;
 
 // module imports
+use backup::Backup;
+use case::{CaseStyle, CaseTransform, Language, CASE_EXCEPTIONS};
 use cli::{CliArgs, Fields};
 use operations::*;
-use tags::FlacTags;
+use replace::Substitution;
+use rules::Rules;
+use schema::Schema;
+use tags::{parse_copy_args, parse_field_args, parse_insert_args, parse_join_args, parse_max_args};
+use tags::{parse_keep_args, validate_field_name, FlacTags, LEGACY_TAGS};
+use tags::{parse_rename_args, parse_tag_args, parse_value_args};
+use template::{PathPattern, Template};
 
 lazy_static::lazy_static! {
     static ref PRATT_PARSER: PrattParser<Rule> = {
@@ -36,7 +53,8 @@ lazy_static::lazy_static! {
 
         PrattParser::new()
             .op(Op::infix(Rule::or, Left))
-            .op(Op::infix(Rule::and, Left) | Op::infix(Rule::equals, Left) | Op::infix(Rule::not_equals, Left)
+            .op(Op::infix(Rule::and, Left))
+            .op(Op::infix(Rule::equals, Left) | Op::infix(Rule::not_equals, Left)
                 | Op::infix(Rule::contains, Left)| Op::infix(Rule::greater, Left)| Op::infix(Rule::greater_eq, Left)
                 | Op::infix(Rule::less, Left)| Op::infix(Rule::less_eq, Left))
             .op(Op::prefix(Rule::not))
@@ -45,104 +63,497 @@ lazy_static::lazy_static! {
 
 fn main() -> Result<(), Box<dyn Error>> {
     let args = CliArgs::parse();
-    let tasks = FlacTags::from_args(args.fields);
+    let custom_tasks = parse_tag_args(&args.fields.tags)?;
+    let set_tasks = parse_tag_args(&args.fields.set_tag)?;
+    let append_tasks = parse_tag_args(&args.fields.append_tag)?;
+    let delete_tasks = parse_tag_args(&args.fields.delete_tag)?;
+    let rename_tasks = parse_rename_args(&args.fields.rename_field)?;
+    let rename_template = match &args.rename {
+        Some(template) => Some(Template::from_str(template)?),
+        None => None,
+    };
+    let values_from = args
+        .values_from
+        .chunks(2)
+        .map(|arg| {
+            let field = arg[0].to_ascii_uppercase();
+            validate_field_name(&field).map(|_| (field, arg[1].clone()))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let path_pattern = match &args.fields.from_path {
+        Some(pattern) => Some(PathPattern::from_str(pattern)?),
+        None => None,
+    };
+    let reference = match &args.fields.tags_from {
+        Some(path) => Some(Tag::read_from_path(path)?),
+        None => None,
+    };
+    let reference_tags = match &reference {
+        Some(tag) => {
+            let fields = args
+                .fields
+                .from_fields
+                .iter()
+                .map(|f| f.trim().to_ascii_uppercase())
+                .collect::<HashSet<_>>();
+            fields.iter().try_for_each(|f| validate_field_name(f))?;
+            let mut meta = tag.vorbis_comments().cloned().unwrap_or_default();
+            meta.comments
+                .retain(|k, _| fields.is_empty() || fields.contains(k));
+            Some(meta)
+        }
+        None => None,
+    };
+    let with_pictures = args.fields.with_pictures;
+    let copy_append = args.fields.copy_append;
+    let keep_patterns = parse_keep_args(&args.keep);
+    let copy_tasks = parse_copy_args(&args.fields.copy)?;
+    let move_tasks = parse_copy_args(&args.fields.move_tag)?;
+    let remove_tasks = parse_value_args(&args.fields.values.remove_value)?;
+    let insert_tasks = parse_insert_args(&args.fields.values.insert_value)?;
+    let dedupe_tasks = parse_field_args(&args.fields.values.dedupe_values)?;
+    let sort_tasks = parse_field_args(&args.fields.values.sort_values)?;
+    let max_tasks = parse_max_args(&args.fields.values.max_values)?;
+    let split_tasks = parse_field_args(&args.fields.values.split)?;
+    let split_delimiters = match args.fields.values.split_delimiter.is_empty() {
+        true => SPLIT_DELIMITERS.iter().map(|d| d.to_string()).collect(),
+        false => args.fields.values.split_delimiter.clone(),
+    };
+    if split_delimiters.iter().any(|d| d.is_empty()) {
+        Err("Split delimiters cannot be empty")?
+    }
+    let join_tasks = parse_join_args(&args.fields.values.join)?;
+    let replace_tasks = args
+        .fields
+        .values
+        .replace
+        .chunks(2)
+        .map(|r| {
+            // `*` replaces in every tag
+            let field = r[0].to_ascii_uppercase();
+            if field != "*" {
+                validate_field_name(&field)?;
+            }
+            Ok((field, Substitution::from_str(&r[1])?))
+        })
+        .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+    let case_exceptions = CASE_EXCEPTIONS
+        .iter()
+        .map(|e| e.to_string())
+        .chain(args.fields.values.case_exception.iter().cloned())
+        .collect::<Vec<_>>();
+    let case_language = Language::from_str(&args.fields.values.case_language)?;
+    let case_tasks = args
+        .fields
+        .values
+        .case
+        .chunks(2)
+        .map(|c| {
+            let field = c[0].to_ascii_uppercase();
+            validate_field_name(&field)?;
+            let style = CaseStyle::from_str(&c[1])?;
+            let case = CaseTransform::new(style, case_language, &case_exceptions);
+            Ok((field, case))
+        })
+        .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+    let mut tasks = FlacTags::from_args(args.fields)
+        .into_iter()
+        .map(|(field, tags)| (field.as_str().to_string(), tags))
+        .collect::<BTreeMap<_, _>>();
+    custom_tasks
+        .into_iter()
+        .for_each(|(field, mut tags)| tasks.entry(field).or_default().append(&mut tags));
+
+    // Renames (including migrating legacy tags) come first, then tags read from paths and copied
+    // from a reference file. Set, append, and delete are parsed as mutually exclusive (with set as
+    // the default), followed by any per-field edits, and then operations on individual values
+    let migrations = match args.migrate {
+        true => LEGACY_TAGS,
+        false => &[],
+    };
+    let edits = migrations
+        .iter()
+        .map(|(old, new)| Edit::Rename(old.to_string(), new.to_string()))
+        .chain(rename_tasks.into_iter().map(|(o, n)| Edit::Rename(o, n)))
+        .chain(path_pattern.map(|p| Edit::FromPath(p, args.append)))
+        .chain(reference_tags.map(|t| Edit::TagsFrom(t, args.append)))
+        .chain(tasks.into_iter().map(|(field, tags)| {
+            if args.append {
+                Edit::Append(field, tags)
+            } else if args.delete {
+                Edit::Delete(field)
+            } else {
+                Edit::Set(field, tags)
+            }
+        }))
+        .chain(delete_tasks.into_keys().map(Edit::Delete))
+        .chain(set_tasks.into_iter().map(|(f, t)| Edit::Set(f, t)))
+        .chain(append_tasks.into_iter().map(|(f, t)| Edit::Append(f, t)))
+        .chain(
+            copy_tasks
+                .into_iter()
+                .map(|(src, dst)| Edit::Copy(src, dst, copy_append)),
+        )
+        .chain(
+            move_tasks
+                .into_iter()
+                .map(|(src, dst)| Edit::Move(src, dst, copy_append)),
+        )
+        .chain(replace_tasks.into_iter().map(|(f, s)| Edit::Replace(f, s)))
+        .chain(case_tasks.into_iter().map(|(f, c)| Edit::ChangeCase(f, c)))
+        .chain(
+            split_tasks
+                .into_iter()
+                .map(|f| Edit::SplitValues(f, split_delimiters.clone())),
+        )
+        .chain(
+            remove_tasks
+                .into_iter()
+                .map(|(f, v)| Edit::RemoveValue(f, v)),
+        )
+        .chain(
+            insert_tasks
+                .into_iter()
+                .map(|(f, i, v)| Edit::InsertValue(f, i, v)),
+        )
+        .chain(dedupe_tasks.into_iter().map(Edit::DedupeValues))
+        .chain(sort_tasks.into_iter().map(Edit::SortValues))
+        .chain(max_tasks.into_iter().map(|(f, m)| Edit::MaxValues(f, m)))
+        .chain(join_tasks.into_iter().map(|(f, s)| Edit::JoinValues(f, s)))
+        .collect::<Vec<_>>();
+    let edits = match args.template {
+        true => edits
+            .into_iter()
+            .map(Edit::into_template)
+            .collect::<Result<Vec<_>, _>>()?,
+        false => edits,
+    };
+    let assignments = args
+        .eval
+        .iter()
+        .map(|s| Assignments::from_str(s))
+        .collect::<Result<Vec<_>, _>>()?;
+    let rules = match &args.rules {
+        Some(path) => Rules::from_path(Path::new(path))?,
+        None => Rules::default(),
+    };
+    let numbered = [
+        (args.auto_number, "TRACKNUMBER"),
+        (args.number_total, "TRACKTOTAL"),
+        (args.number_discs, "DISCNUMBER"),
+        (args.number_total && args.number_discs, "DISCTOTAL"),
+    ]
+    .into_iter()
+    .filter_map(|(numbered, field)| numbered.then_some(field));
+    let from_file = values_from.iter().map(|(f, _)| f.as_str()).chain(
+        args.title_from_file
+            .iter()
+            .flat_map(|_| ["TITLE", "ARTIST"]),
+    );
+    let edited = edits
+        .iter()
+        .map(|e| e.field())
+        .chain(assignments.iter().flat_map(Assignments::fields))
+        .chain(rules.fields())
+        .chain(numbered)
+        .chain(from_file)
+        .map(str::to_string)
+        .collect::<Vec<_>>();
     let mut paths = Vec::new();
 
+    let schema = match &args.schema {
+        Some(path) => Schema::from_path(Path::new(path))?,
+        None => Schema::default(),
+    };
+    let options = EvalOptions {
+        strict: args.strict,
+        schema: &schema,
+    };
+
+    let restore = match &args.restore {
+        Some(path) => Backup::from_path(Path::new(path))?,
+        None => Backup::default(),
+    };
+    restore
+        .files()
+        .for_each(|(p, _)| paths.push((None, p.to_owned())));
+
+    // rules apply to the whole library, unless given files or a query
+    if args.rules.is_some() && args.arguments.files.is_none() && args.arguments.query.is_none() {
+        library_files().for_each(|(t, p)| paths.push((Some(t), p)));
+    }
+
     if let Some(files) = args.arguments.files {
-        files.iter().map(PathBuf::from).for_each(|p| paths.push(p));
+        files
+            .iter()
+            .map(PathBuf::from)
+            .for_each(|p| paths.push((None, p)));
     }
 
     // handle query
     if let Some(query_str) = args.arguments.query {
         // build expression
-        let grammer = QueryParser::parse_grammer(&query_str)?;
-        let expression = build(grammer)?;
+        let query = Query::from_str(&query_str)?;
 
         // evaulate against all files recursively
-        let mut buffer = Vec::new();
-        let cwd = std::fs::read_dir("./").unwrap();
-        get_paths(cwd, &mut buffer);
-
-        buffer
-            .into_iter()
-            // evaluate expressions
-            .filter_map(|p| {
-                let t = Tag::read_from_path(p.as_path()).ok()?;
-                let env = t.vorbis_comments()?;
-                Some(expression.eval(env).map(|v| (v, p)))
-            })
-            // catch any errors
-            .collect::<Result<Vec<(Value, PathBuf)>, QueryEvalError>>()?
-            .into_iter()
-            .map(|(v, p)| match v {
-                Value::Boolean(b) => Ok((b, p)),
-                _ => Err(QueryEvalError::BadEvaluation)?,
-            })
-            // catch any invalidate types
-            .collect::<Result<Vec<_>, QueryEvalError>>()?
-            .into_iter()
-            .filter_map(|(v, p)| if v { Some(p) } else { None })
-            // append matching queries' paths
-            .for_each(|p| paths.push(p));
+        if query.is_grouped() {
+            // groups need every file at once, so only what the query reads is kept of each, and
+            // the matching files are read again
+            let (files, library): (Vec<_>, Vec<_>) = library_files()
+                .map(|(t, p)| (Metadata::from(&t), p))
+                .unzip();
+            let matches = query.eval_all(&files, options)?;
+            library
+                .into_iter()
+                .zip(matches)
+                .filter(|(_, v)| *v)
+                .for_each(|(p, _)| paths.push((None, p)));
+        } else {
+            // otherwise each file is evaluated on its own, keeping only the matching files
+            for (t, p) in library_files() {
+                let matches = query.eval_all(&[Metadata::from(&t)], options)?;
+                if matches[0] {
+                    paths.push((Some(t), p));
+                }
+            }
+        }
     };
 
-    // open meta data files
+    // open meta data files not already read, once each however many times they were given
     let mut paths = paths
-        .iter()
-        .map(|p| Tag::read_from_path(p.as_path()).map(|t| (t, p)))
+        .into_iter()
+        .unique_by(|(_, p)| p.canonicalize().unwrap_or(p.to_path_buf()))
+        .map(|(t, p)| match t {
+            Some(t) => Ok((t, p)),
+            None => Tag::read_from_path(p.as_path()).map(|t| (t, p)),
+        })
         .collect::<Result<Vec<(_, _)>, _>>()?;
 
-    // apply each edit to every file
-    tasks.into_iter().for_each(|(field, tags)| {
-        paths.iter_mut().for_each(|(tag, path)| {
-            let mut meta = tag.vorbis_comments_mut();
-            // Set, append, and delete are parsed as mutually exclusive
-            if args.set {
-                /* set */
-                set_tags(meta, field, tags.to_owned());
-            } else if args.append {
-                /* append */
-                append_tags(meta, field, tags.to_owned());
-            } else if args.delete {
-                /* delete */
-                delete_tags(meta, field);
-            }
-        });
+    // keep the original tags (and pictures, if replacing them), to report the changes of a dry run
+    let originals = paths
+        .iter()
+        .map(|(t, _)| t.vorbis_comments().cloned().unwrap_or_default())
+        .collect::<Vec<_>>();
+    let original_pictures = paths
+        .iter()
+        .map(|(t, _)| match with_pictures {
+            true => t.pictures().cloned().collect(),
+            false => Vec::new(),
+        })
+        .collect::<Vec<Vec<_>>>();
+
+    // canonical paths, for matching backed up files and the path variables of templates
+    let canonicals = paths
+        .iter()
+        .map(|(_, p)| p.canonicalize().unwrap_or(p.to_path_buf()))
+        .collect::<Vec<_>>();
+
+    // apply every edit to each file (after restoring any backed up tags), in a single pass
+    paths.iter_mut().enumerate().for_each(|(i, (tag, _))| {
+        let mut meta = tag.vorbis_comments_mut();
+        restore
+            .files()
+            .filter(|(p, _)| **p == canonicals[i])
+            .flat_map(|(_, tags)| tags.iter())
+            .for_each(|(k, v)| merge_values(meta, k, v));
+        edits
+            .iter()
+            .for_each(|edit| edit.apply(meta, &originals[i], &canonicals[i]));
+        if let Some(reference) = reference.as_ref().filter(|_| with_pictures) {
+            pictures_from(tag, reference);
+        }
     });
 
-    // optionally performing cleaning
-    paths.iter_mut().for_each(|(tag, path)| {
+    // set tags computed by expressions
+    for (tag, path) in paths.iter_mut() {
+        for a in assignments.iter() {
+            a.apply(tag, options)?
+                .iter()
+                .for_each(|e| print_skipped(path, e));
+        }
+    }
+
+    // apply each rule to the files matching its query, as left by the rules before it
+    let mut matched = vec![0; rules.0.len()];
+    for (rule, matched) in rules.0.iter().zip(matched.iter_mut()) {
+        let files = paths
+            .iter()
+            .map(|(t, _)| Metadata::from(t))
+            .collect::<Vec<_>>();
+        let matches = rule.when.eval_all_set(&files, options)?;
+        for (i, (tag, path)) in paths.iter_mut().enumerate().filter(|(i, _)| matches[*i]) {
+            let meta = tag.vorbis_comments_mut();
+            rule.edits
+                .iter()
+                .for_each(|edit| edit.apply(meta, &originals[i], &canonicals[i]));
+            if let Some(assignments) = &rule.assignments {
+                assignments
+                    .apply(tag, options)?
+                    .iter()
+                    .for_each(|e| print_skipped(path, e));
+            }
+            *matched += 1;
+        }
+    }
+
+    // set values from lines of a file, in order of the files
+    if !values_from.is_empty() || args.title_from_file.is_some() {
+        let files = paths
+            .iter_mut()
+            .map(|(t, p)| (p.as_path(), t.vorbis_comments_mut()))
+            .collect::<Vec<_>>();
+        let order = numbering::ordered_by_directory(&files, None, |m| &**m)
+            .into_iter()
+            .flat_map(|(_, files)| files)
+            .collect::<Vec<_>>();
+        let mut files = files.into_iter().map(|(_, m)| m).collect::<Vec<_>>();
+
+        for (field, path) in &values_from {
+            let lines = tracklist::read_tracklist(Path::new(path), order.len())?;
+            let values = tracklist::parse_values(&lines);
+            order.iter().zip(values).for_each(|(i, value)| {
+                set_tags(files[*i], field, vec![value]);
+            });
+        }
+        if let Some(path) = &args.title_from_file {
+            let lines = tracklist::read_tracklist(Path::new(path), order.len())?;
+            let values = tracklist::parse_values(&lines);
+            order.iter().zip(values).for_each(|(i, value)| {
+                let (artist, title) = match args.with_artist {
+                    true => tracklist::split_artist(&value),
+                    false => (None, value),
+                };
+                set_tags(files[*i], FlacTags::Title, vec![title]);
+                if let Some(artist) = artist {
+                    set_tags(files[*i], FlacTags::Artist, vec![artist]);
+                }
+            });
+        }
+    }
+
+    // number the files of each directory
+    if args.auto_number {
+        let number_by = args.number_by.map(|f| f.to_uppercase());
+        let mut files = paths
+            .iter_mut()
+            .map(|(t, p)| (p.as_path(), t.vorbis_comments_mut()))
+            .collect::<Vec<_>>();
+        numbering::auto_number(
+            &mut files,
+            number_by.as_deref(),
+            args.number_total,
+            args.number_discs,
+        );
+    }
+
+    // optionally performing cleaning, reporting and backing up removed tags
+    let print_removals = !args.dry_run || args.list || args.list_detailed;
+    let mut backup = Backup::default();
+    paths.iter_mut().enumerate().for_each(|(i, (tag, path))| {
         let mut meta = tag.vorbis_comments_mut();
         if args.clean {
             /* clean */
-            clean_tags(meta);
+            clean_tags(meta, args.fold_case);
         } else if args.clean_all {
-            /* clean non-standard tags */
-            clean_non_standard_tags(meta);
+            /* clean duplicates and non-standard tags */
+            clean_tags(meta, args.fold_case);
+            let removed = clean_non_standard_tags(meta, &keep_patterns);
+            if print_removals {
+                print_removed(path, &removed);
+            }
+            backup.record(&canonicals[i], removed);
         }
     });
 
-    // print listing information and save
-    paths.into_iter().try_for_each(|(mut t, p)| {
-        if args.list {
-            /* print filenames */
-            list(p.as_path());
-        } else if args.list_detailed {
-            /* print filename & all current tags */
-            list_detailed(p.as_path(), t.vorbis_comments_mut());
+    // validate edited tags against their declared types, before any file is saved
+    paths.iter_mut().try_for_each(|(tag, _)| {
+        let meta = tag.vorbis_comments_mut();
+        let fields = match edited.iter().any(|f| f == "*") {
+            true => meta.comments.keys().cloned().collect::<Vec<_>>(),
+            false => edited.clone(),
+        };
+        fields.iter().try_for_each(|f| match meta.get(f) {
+            Some(values) => schema.validate(f, values, args.strict),
+            None => Ok(()),
+        })
+    })?;
+
+    if let Some(path) = &args.backup {
+        if !args.dry_run && !backup.is_empty() {
+            backup.write(Path::new(path))?;
         }
+    }
 
-        /* save meta data */
-        if !args.dry_run {
-            t.save()?;
+    // plan any renames before saving, so nothing is written if the files can't be renamed
+    let renames = match &rename_template {
+        Some(template) => {
+            let files = paths
+                .iter()
+                .filter_map(|(t, p)| Some((p.as_path(), t.vorbis_comments()?)))
+                .collect::<Vec<_>>();
+            rename::plan_renames(&files, template)?
         }
+        None => Vec::new(),
+    };
 
-        Ok::<(), metaflac::Error>(())
-    })?;
+    // print listing information and save
+    paths
+        .into_iter()
+        .zip(originals.iter().zip(original_pictures.iter()))
+        .try_for_each(|((mut t, p), (original, original_pictures))| {
+            let pictures = match with_pictures {
+                true => (original_pictures.iter().collect(), t.pictures().collect()),
+                false => (Vec::new(), Vec::new()),
+            };
+            let pictures_changed = pictures.0 != pictures.1;
+            if args.list {
+                /* print filenames */
+                list(p.as_path());
+            } else if args.list_detailed {
+                /* print filename & all current tags */
+                list_detailed(p.as_path(), t.vorbis_comments_mut());
+            } else if args.dry_run {
+                /* print the tags that would change */
+                let pictures = (&pictures.0[..], &pictures.1[..]);
+                let empty = VorbisComment::new();
+                let after = t.vorbis_comments().unwrap_or(&empty);
+                print_changes(p.as_path(), original, after, pictures);
+            }
+
+            /* save meta data, unless unchanged */
+            if !args.dry_run && (t.vorbis_comments() != Some(original) || pictures_changed) {
+                t.save()?;
+            }
+
+            Ok::<(), metaflac::Error>(())
+        })?;
+
+    if args.dry_run && !args.list && !args.list_detailed {
+        rules::print_matches(&rules, &matched);
+    }
+
+    // move renamed files, after their tags have been saved
+    if !args.dry_run {
+        rename::rename_files(&renames)?;
+    } else if !args.list {
+        rename::print_renames(&renames);
+    }
 
     Ok(())
 }
 
+/// Reads every file with tags in the current directory, recursively.
+fn library_files() -> impl Iterator<Item = (Tag, PathBuf)> {
+    let mut buffer = Vec::new();
+    let cwd = std::fs::read_dir("./").unwrap();
+    get_paths(cwd, &mut buffer);
+
+    buffer
+        .into_iter()
+        .filter_map(|p| Tag::read_from_path(p.as_path()).ok().map(|t| (t, p)))
+        .filter(|(t, _)| t.vorbis_comments().is_some())
+}
+
 fn get_paths(dir: ReadDir, buffer: &mut Vec<PathBuf>) {
     for path in dir {
         let p = path.unwrap().path();
@@ -0,0 +1,276 @@
+use itertools::Itertools;
+use lazy_static::lazy_static;
+use metaflac::block::VorbisComment;
+use regex::Regex;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+lazy_static! {
+    /// Directory names of a single disc, e.g. `CD1`, `Disc 2`, or `disk_3 (Bonus)`.
+    static ref DISC_DIRECTORY: Regex = Regex::new(r"(?i)^(?:cd|dis[ck])[\s_-]*\d+\b").unwrap();
+}
+
+/// Compares strings in natural order, where runs of digits are compared by their value (so `2`
+/// comes before `10`) and all other text is compared ignoring case.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let chunks = |s: &str| {
+        s.chars()
+            .group_by(|c| c.is_ascii_digit())
+            .into_iter()
+            .map(|(_, c)| c.collect::<String>())
+            .collect::<Vec<_>>()
+    };
+    let (a, b) = (chunks(a), chunks(b));
+    for (x, y) in a.iter().zip(b.iter()) {
+        let ordering = match (x.parse::<u128>(), y.parse::<u128>()) {
+            (Ok(i), Ok(j)) => i.cmp(&j).then(x.len().cmp(&y.len())),
+            _ => x.to_lowercase().cmp(&y.to_lowercase()),
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+
+    a.len().cmp(&b.len())
+}
+
+/// Groups files by their directory, ordering each directory's files in natural order by the first
+/// value of a tag (with unset values last), or by file name if no tag is given. Directories are
+/// also in natural order.
+pub fn ordered_by_directory<'a, T>(
+    files: &'a [(&'a Path, T)],
+    by: Option<&str>,
+    meta: impl Fn(&T) -> &VorbisComment,
+) -> Vec<(PathBuf, Vec<usize>)> {
+    let key = |i: &usize| {
+        let (path, tags) = &files[*i];
+        let value = by.and_then(|f| meta(tags).get(f)?.first().cloned());
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        (value, name.into_owned())
+    };
+
+    (0..files.len())
+        .into_group_map_by(|i| directory(files[*i].0))
+        .into_iter()
+        .sorted_by(|(a, _), (b, _)| natural_cmp(&a.to_string_lossy(), &b.to_string_lossy()))
+        .map(|(dir, files)| {
+            let files = files
+                .into_iter()
+                .sorted_by(|a, b| match (key(a), key(b)) {
+                    ((Some(x), m), (Some(y), n)) => natural_cmp(&x, &y).then(natural_cmp(&m, &n)),
+                    ((Some(_), _), (None, _)) => Ordering::Less,
+                    ((None, _), (Some(_), _)) => Ordering::Greater,
+                    ((None, m), (None, n)) => natural_cmp(&m, &n),
+                })
+                .collect();
+            (dir, files)
+        })
+        .collect()
+}
+
+/// Numbers the files of each directory from 1, setting `TRACKNUMBER` (and `TRACKTOTAL` if
+/// `total` is set) in the order given by [`ordered_by_directory`].
+///
+/// If `discs` is set, directories sharing a parent directory are also numbered as discs from 1,
+/// setting `DISCNUMBER` (and `DISCTOTAL` if `total` is set), when they are discs of the same album.
+/// That is, when their files share an `ALBUM` (and `ALBUMARTIST`), or their names look like discs
+/// (e.g. `CD1` and `CD2`). Other directories, such as albums sharing an artist's directory, aren't
+/// numbered as discs.
+pub fn auto_number(
+    files: &mut [(&Path, &mut VorbisComment)],
+    by: Option<&str>,
+    total: bool,
+    discs: bool,
+) {
+    let directories = ordered_by_directory(files, by, |m| &**m);
+    let disc_sets = directories
+        .iter()
+        .map(|(dir, indices)| disc_set(dir, indices.iter().map(|i| &*files[*i].1)))
+        .collect::<Vec<_>>();
+    let disc_totals = disc_sets.iter().flatten().counts();
+    let mut disc_numbers: HashMap<&DiscSet, usize> = HashMap::new();
+
+    for ((dir, indices), set) in directories.iter().zip(disc_sets.iter()) {
+        // a directory alone in its set is only a disc if it's named as one
+        let disc = set
+            .as_ref()
+            .filter(|set| disc_totals[set] > 1 || is_disc_directory(dir))
+            .map(|set| {
+                let disc = disc_numbers.entry(set).or_default();
+                *disc += 1;
+                (*disc, disc_totals[set])
+            });
+
+        for (n, i) in indices.iter().enumerate() {
+            let meta = &mut *files[*i].1;
+            meta.set("TRACKNUMBER", vec![(n + 1).to_string()]);
+            if total {
+                meta.set("TRACKTOTAL", vec![indices.len().to_string()]);
+            }
+            if let Some((disc, disc_total)) = disc.filter(|_| discs) {
+                meta.set("DISCNUMBER", vec![disc.to_string()]);
+                if total {
+                    meta.set("DISCTOTAL", vec![disc_total.to_string()]);
+                }
+            }
+        }
+    }
+}
+
+/// The directories numbered together as the discs of an album, within a parent directory.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum DiscSet {
+    /// Directories whose files all share an album artist (if set) and album.
+    Album(PathBuf, Option<String>, String),
+    /// Directories named as discs, whose files don't share an album.
+    Named(PathBuf),
+}
+
+fn disc_set<'a>(dir: &Path, metas: impl Iterator<Item = &'a VorbisComment>) -> Option<DiscSet> {
+    let first = |m: &VorbisComment, f: &str| m.get(f).and_then(|v| v.first().cloned());
+    let album = metas
+        .map(|m| (first(m, "ALBUMARTIST"), first(m, "ALBUM")))
+        .all_equal_value()
+        .ok();
+
+    let parent = directory(dir);
+    match album {
+        Some((artist, Some(album))) => Some(DiscSet::Album(parent, artist, album)),
+        _ if is_disc_directory(dir) => Some(DiscSet::Named(parent)),
+        _ => None,
+    }
+}
+
+fn is_disc_directory(dir: &Path) -> bool {
+    let name = dir.file_name().unwrap_or_default().to_string_lossy();
+    DISC_DIRECTORY.is_match(&name)
+}
+
+fn directory(path: &Path) -> PathBuf {
+    let path = path.canonicalize().unwrap_or(path.to_path_buf());
+    path.parent().map(Path::to_path_buf).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_natural_cmp() {
+        let sorted = [
+            "Track 10.flac",
+            "track 2.flac",
+            "Track 1.flac",
+            "02.flac",
+            "2.flac",
+            "b",
+        ]
+        .into_iter()
+        .sorted_by(|a, b| natural_cmp(a, b))
+        .collect::<Vec<_>>();
+
+        assert_eq!(
+            sorted,
+            [
+                "2.flac",
+                "02.flac",
+                "b",
+                "Track 1.flac",
+                "track 2.flac",
+                "Track 10.flac"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_auto_number() {
+        let paths = [
+            "Album/CD2/1.flac",
+            "Album/CD1/10.flac",
+            "Album/CD1/9.flac",
+            "Single/a.flac",
+        ]
+        .map(PathBuf::from);
+        let mut metas = [0, 1, 2, 3].map(|_| VorbisComment::new());
+        metas[3].set("TRACKNUMBER", vec!["7"]);
+        let mut files = paths
+            .iter()
+            .map(|p| p.as_path())
+            .zip(metas.iter_mut())
+            .collect::<Vec<_>>();
+        auto_number(&mut files, None, true, true);
+
+        assert_eq!(
+            numbers(&metas),
+            ["2 2 1 1", "1 2 2 2", "1 2 1 2", "- - 1 1"]
+        );
+    }
+
+    #[test]
+    fn test_auto_number_albums() {
+        let paths = [
+            "Nujabes/Modal Soul/1.flac",
+            "Nujabes/Metaphorical Music/1.flac",
+            "Nujabes/Metaphorical Music/2.flac",
+            "Hydeout/Part 1/1.flac",
+            "Hydeout/Part 2/1.flac",
+        ]
+        .map(PathBuf::from);
+        let mut metas = [0, 1, 2, 3, 4].map(|_| VorbisComment::new());
+        metas[0].set("ALBUM", vec!["Modal Soul"]);
+        metas[1].set("ALBUM", vec!["Metaphorical Music"]);
+        metas[2].set("ALBUM", vec!["Metaphorical Music"]);
+        for m in metas[3..].iter_mut() {
+            m.set("ALBUMARTIST", vec!["Various Artists"]);
+            m.set("ALBUM", vec!["Hydeout Productions"]);
+        }
+        let mut files = paths
+            .iter()
+            .map(|p| p.as_path())
+            .zip(metas.iter_mut())
+            .collect::<Vec<_>>();
+        auto_number(&mut files, None, true, true);
+
+        // albums sharing an artist's directory aren't discs, while directories of one album are
+        assert_eq!(
+            numbers(&metas),
+            ["- - 1 1", "- - 1 2", "- - 2 2", "1 2 1 1", "2 2 1 1"]
+        );
+    }
+
+    fn numbers(metas: &[VorbisComment]) -> Vec<String> {
+        let tags = |m: &VorbisComment, f: &str| match m.get(f) {
+            Some(values) => values[0].clone(),
+            None => "-".to_string(),
+        };
+        metas
+            .iter()
+            .map(|m| {
+                [
+                    tags(m, "DISCNUMBER"),
+                    tags(m, "DISCTOTAL"),
+                    tags(m, "TRACKNUMBER"),
+                    tags(m, "TRACKTOTAL"),
+                ]
+                .join(" ")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_ordered_by_tag() {
+        let mut metas = [0, 1, 2].map(|_| VorbisComment::new());
+        metas[0].set("SIDE", vec!["B1"]);
+        metas[2].set("SIDE", vec!["A2"]);
+        let paths = ["x/1.flac", "x/2.flac", "x/3.flac"].map(PathBuf::from);
+        let files = paths
+            .iter()
+            .map(|p| p.as_path())
+            .zip(metas.iter())
+            .collect::<Vec<_>>();
+
+        let ordered = ordered_by_directory(&files, Some("SIDE"), |m| *m);
+        assert_eq!(ordered, [(PathBuf::from("x"), vec![2, 0, 1])]);
+    }
+}
@@ -1,18 +1,131 @@
+use crate::case::CaseTransform;
+use crate::query::QueryEvalError;
+use crate::replace::Substitution;
 use crate::tags::FlacTags;
+use crate::template::{PathPattern, Template, TemplateError};
 use colored::Colorize;
 use itertools::Itertools;
-use metaflac::block::VorbisComment;
+use metaflac::block::{Block, BlockType, Picture, VorbisComment};
+use metaflac::Tag;
+use regex::Regex;
+use std::collections::BTreeMap;
 use std::path::Path;
 use std::str::FromStr;
+use unicode_normalization::UnicodeNormalization;
+
+/// An edit to a single field, applied to every matching file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Edit {
+    Set(String, Vec<String>),
+    Append(String, Vec<String>),
+    Delete(String),
+    RemoveValue(String, String),
+    InsertValue(String, usize, String),
+    DedupeValues(String),
+    SortValues(String),
+    MaxValues(String, usize),
+    /// Renames the first field to the second, merging their values.
+    Rename(String, String),
+    /// Tags read from the file's path, appending if set.
+    FromPath(PathPattern, bool),
+    /// Copies the values of the first field to the second, appending if set.
+    Copy(String, String, bool),
+    /// Moves the values of the first field to the second, appending if set.
+    Move(String, String, bool),
+    /// Substitution applied to every value of the field, or of every field if the field is `*`.
+    Replace(String, Substitution),
+    /// Tags copied from a reference file, appending if set.
+    TagsFrom(VorbisComment, bool),
+    /// Case change applied to every value of the field.
+    ChangeCase(String, CaseTransform),
+    /// Splits each value of the field at any of the delimiters.
+    SplitValues(String, Vec<String>),
+    /// Joins the values of the field with the separator.
+    JoinValues(String, String),
+    /// Values rendered from the file's original tags, appending if set.
+    Template(String, Vec<Template>, bool),
+}
+impl Edit {
+    pub fn field(&self) -> &str {
+        match self {
+            Edit::Set(field, _)
+            | Edit::Append(field, _)
+            | Edit::Delete(field)
+            | Edit::RemoveValue(field, _)
+            | Edit::InsertValue(field, _, _)
+            | Edit::DedupeValues(field)
+            | Edit::SortValues(field)
+            | Edit::MaxValues(field, _)
+            | Edit::Replace(field, _)
+            | Edit::ChangeCase(field, _)
+            | Edit::SplitValues(field, _)
+            | Edit::JoinValues(field, _)
+            | Edit::Template(field, _, _)
+            | Edit::Rename(_, field)
+            | Edit::Copy(_, field, _)
+            | Edit::Move(_, field, _) => field,
+            Edit::FromPath(..) | Edit::TagsFrom(..) => "*",
+        }
+    }
+
+    /// Reads the values of a set or append as templates, leaving other edits unchanged.
+    pub fn into_template(self) -> Result<Edit, TemplateError> {
+        let (field, values, append) = match self {
+            Edit::Set(field, values) => (field, values, false),
+            Edit::Append(field, values) => (field, values, true),
+            edit => return Ok(edit),
+        };
+        let templates = values
+            .iter()
+            .map(|v| Template::from_str(v))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Edit::Template(field, templates, append))
+    }
+
+    /// Applies the edit to a file's tags, where templates are rendered from the tags the file was
+    /// read with (so they don't depend on the order of edits), and `path` is the file's canonical
+    /// path.
+    pub fn apply(&self, meta: &mut VorbisComment, original: &VorbisComment, path: &Path) {
+        match self {
+            Edit::Set(field, tags) => set_tags(meta, field, tags.to_owned()),
+            Edit::Append(field, tags) => append_tags(meta, field, tags.to_owned()),
+            Edit::Delete(field) => delete_tags(meta, field),
+            Edit::RemoveValue(field, value) => remove_value(meta, field, value),
+            Edit::InsertValue(field, position, value) => {
+                insert_value(meta, field, *position, value.to_owned())
+            }
+            Edit::DedupeValues(field) => dedupe_values(meta, field),
+            Edit::SortValues(field) => sort_values(meta, field),
+            Edit::MaxValues(field, max) => truncate_values(meta, field, *max),
+            Edit::Replace(field, sub) => replace_values(meta, field, sub),
+            Edit::Rename(old, new) => rename_field(meta, old, new),
+            Edit::FromPath(pattern, append) => tags_from_path(meta, pattern, path, *append),
+            Edit::Copy(src, dst, append) => copy_tags(meta, src, dst, *append),
+            Edit::Move(src, dst, append) => move_tags(meta, src, dst, *append),
+            Edit::TagsFrom(reference, append) => tags_from(meta, reference, *append),
+            Edit::ChangeCase(field, case) => change_case(meta, field, case),
+            Edit::SplitValues(field, delimiters) => split_values(meta, field, delimiters),
+            Edit::JoinValues(field, separator) => join_values(meta, field, separator),
+            Edit::Template(field, templates, append) => {
+                let tags = templates.iter().map(|t| t.render(original, path)).collect();
+                match append {
+                    true => append_tags(meta, field, tags),
+                    false => set_tags(meta, field, tags),
+                }
+            }
+        }
+    }
+}
 
 /// Sets tags for the given field.
-pub fn set_tags(meta: &mut VorbisComment, field: FlacTags, tags: Vec<String>) {
-    meta.set(field.as_str(), tags)
+pub fn set_tags(meta: &mut VorbisComment, field: impl AsRef<str>, tags: Vec<String>) {
+    meta.set(field.as_ref(), tags)
 }
 
 /// Append tags to the given field.
-pub fn append_tags(meta: &mut VorbisComment, field: FlacTags, mut tags: Vec<String>) {
-    let curr_tags = meta.get(field.as_str());
+pub fn append_tags(meta: &mut VorbisComment, field: impl AsRef<str>, mut tags: Vec<String>) {
+    let curr_tags = meta.get(field.as_ref());
 
     let new_tags = if let Some(curr_tags) = curr_tags {
         let mut new_tags = curr_tags.to_owned();
@@ -22,41 +135,283 @@ pub fn append_tags(meta: &mut VorbisComment, field: FlacTags, mut tags: Vec<Stri
         tags
     };
 
-    meta.set(field.as_str(), new_tags);
+    meta.set(field.as_ref(), new_tags);
 }
 
 /// Deletes tags for given field
-pub fn delete_tags(meta: &mut VorbisComment, field: FlacTags) {
-    meta.remove(field.as_str())
+pub fn delete_tags(meta: &mut VorbisComment, field: impl AsRef<str>) {
+    meta.remove(field.as_ref())
+}
+
+/// Removes every occurence of a value from the given field, deleting the field if no values remain.
+pub fn remove_value(meta: &mut VorbisComment, field: impl AsRef<str>, value: &str) {
+    meta.remove_pair(field.as_ref(), value)
+}
+
+/// Inserts a value at the given (1-indexed) position of the field, or at the end if the field has
+/// fewer values.
+pub fn insert_value(
+    meta: &mut VorbisComment,
+    field: impl AsRef<str>,
+    position: usize,
+    value: String,
+) {
+    let mut tags = meta.get(field.as_ref()).cloned().unwrap_or_default();
+    tags.insert(position.saturating_sub(1).min(tags.len()), value);
+
+    meta.set(field.as_ref(), tags);
+}
+
+/// Removes values of the field that only differ by case, keeping the first occurence.
+pub fn dedupe_values(meta: &mut VorbisComment, field: impl AsRef<str>) {
+    if let Some(tags) = meta.get(field.as_ref()) {
+        let tags = tags
+            .iter()
+            .unique_by(|t| t.to_lowercase())
+            .cloned()
+            .collect::<Vec<_>>();
+        meta.set(field.as_ref(), tags);
+    }
+}
+
+/// Sorts the values of the field alphabetically (ignoring case).
+pub fn sort_values(meta: &mut VorbisComment, field: impl AsRef<str>) {
+    if let Some(tags) = meta.get(field.as_ref()) {
+        let tags = tags
+            .iter()
+            .sorted_by_key(|t| t.to_lowercase())
+            .cloned()
+            .collect::<Vec<_>>();
+        meta.set(field.as_ref(), tags);
+    }
+}
+
+/// Caps the number of values of the field, keeping the first values.
+pub fn truncate_values(meta: &mut VorbisComment, field: impl AsRef<str>, max: usize) {
+    if let Some(tags) = meta.get(field.as_ref()) {
+        let tags = tags.iter().take(max).cloned().collect::<Vec<_>>();
+        match tags.is_empty() {
+            true => meta.remove(field.as_ref()),
+            false => meta.set(field.as_ref(), tags),
+        }
+    }
+}
+
+/// Delimiters between several values stored as one, e.g. `Nujabes feat. Shing02`.
+pub const SPLIT_DELIMITERS: &[&str] = &[";", " / ", " feat. ", " ft. "];
+
+/// Splits each value of the field at any of the delimiters (ignoring case) into several values,
+/// trimming each value and removing any left empty.
+pub fn split_values(meta: &mut VorbisComment, field: impl AsRef<str>, delimiters: &[String]) {
+    let pattern = delimiters.iter().map(|d| regex::escape(d)).join("|");
+    let delimiter = Regex::new(&format!("(?i){pattern}")).expect("Delimiters are escaped");
+    if let Some(tags) = meta.get(field.as_ref()) {
+        let tags = tags
+            .iter()
+            .flat_map(|t| delimiter.split(t))
+            .map(str::trim)
+            .filter(|t| !t.is_empty())
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        match tags.is_empty() {
+            true => meta.remove(field.as_ref()),
+            false => meta.set(field.as_ref(), tags),
+        }
+    }
+}
+
+/// Joins the values of the field into a single value, for players only showing the first value.
+pub fn join_values(meta: &mut VorbisComment, field: impl AsRef<str>, separator: &str) {
+    if let Some(tags) = meta.get(field.as_ref()) {
+        let tags = vec![tags.join(separator)];
+        meta.set(field.as_ref(), tags);
+    }
 }
 
-/// Removes duplicated tags
-pub fn clean_tags(meta: &mut VorbisComment) {
-    let new_values = meta
+/// Renames a field, merging its values into any existing values of the new field (without
+/// duplicating values). Nothing is changed if the field is unset.
+pub fn rename_field(meta: &mut VorbisComment, old: impl AsRef<str>, new: impl AsRef<str>) {
+    if old.as_ref() == new.as_ref() {
+        return;
+    }
+
+    if let Some(tags) = meta.get(old.as_ref()).cloned() {
+        merge_values(meta, new, &tags);
+        meta.remove(old.as_ref());
+    }
+}
+
+/// Appends values to the field, skipping any values the field already has.
+pub fn merge_values(meta: &mut VorbisComment, field: impl AsRef<str>, tags: &[String]) {
+    let tags = meta
+        .get(field.as_ref())
+        .into_iter()
+        .flatten()
+        .chain(tags.iter())
+        .unique()
+        .cloned()
+        .collect::<Vec<_>>();
+    meta.set(field.as_ref(), tags);
+}
+
+/// Sets (or appends to) the tags read from the file's path. Nothing is changed if the path doesn't
+/// match the pattern.
+pub fn tags_from_path(meta: &mut VorbisComment, pattern: &PathPattern, path: &Path, append: bool) {
+    let tags = pattern
+        .parse(path)
+        .unwrap_or_default()
+        .into_iter()
+        .into_group_map();
+    tags.into_iter().for_each(|(field, tags)| match append {
+        true => append_tags(meta, field, tags),
+        false => set_tags(meta, field, tags),
+    });
+}
+
+/// Copies the values of one field to another, either replacing or appending to the destination's
+/// values. Nothing is changed if the source field is unset.
+pub fn copy_tags(
+    meta: &mut VorbisComment,
+    src: impl AsRef<str>,
+    dst: impl AsRef<str>,
+    append: bool,
+) {
+    if let Some(tags) = meta.get(src.as_ref()).cloned() {
+        match append {
+            true => append_tags(meta, dst, tags),
+            false => set_tags(meta, dst, tags),
+        }
+    }
+}
+
+/// Changes the case of every value of the given field.
+pub fn change_case(meta: &mut VorbisComment, field: impl AsRef<str>, case: &CaseTransform) {
+    if let Some(tags) = meta.get(field.as_ref()) {
+        let tags = tags.iter().map(|t| case.apply(t)).collect::<Vec<_>>();
+        meta.set(field.as_ref(), tags);
+    }
+}
+
+/// Copies every tag of a reference file, either replacing or appending to each tag's values.
+/// Tags the reference doesn't have are left unchanged.
+pub fn tags_from(meta: &mut VorbisComment, reference: &VorbisComment, append: bool) {
+    reference
         .comments
         .iter()
+        .sorted()
+        .for_each(|(field, tags)| match append {
+            true => append_tags(meta, field, tags.to_owned()),
+            false => set_tags(meta, field, tags.to_owned()),
+        });
+}
+
+/// Replaces the pictures of a file with those of a reference file.
+pub fn pictures_from(tag: &mut Tag, reference: &Tag) {
+    tag.remove_blocks(BlockType::Picture);
+    reference
+        .pictures()
+        .cloned()
+        .for_each(|p| tag.push_block(Block::Picture(p)));
+}
+
+/// Moves the values of one field to another, either replacing or appending to the destination's
+/// values. Nothing is changed if the source field is unset.
+pub fn move_tags(
+    meta: &mut VorbisComment,
+    src: impl AsRef<str>,
+    dst: impl AsRef<str>,
+    append: bool,
+) {
+    if src.as_ref() != dst.as_ref() && meta.get(src.as_ref()).is_some() {
+        copy_tags(meta, src.as_ref(), dst, append);
+        delete_tags(meta, src);
+    }
+}
+
+/// Applies a substitution to every value of the field. If the field is `*`, it is applied to every
+/// value of every field.
+pub fn replace_values(meta: &mut VorbisComment, field: impl AsRef<str>, sub: &Substitution) {
+    let fields = match field.as_ref() {
+        "*" => meta.comments.keys().cloned().collect::<Vec<_>>(),
+        field => vec![field.to_string()],
+    };
+
+    fields.into_iter().for_each(|field| {
+        if let Some(tags) = meta.get(&field) {
+            let tags = tags.iter().map(|t| sub.apply(t)).collect::<Vec<_>>();
+            meta.set(field, tags);
+        }
+    });
+}
+
+/// Removes duplicated tags. Values are normalized first (see [`normalize_value`]), removing any
+/// left empty. If `fold_case` is set, values that only differ by case are also duplicates, keeping
+/// the first.
+pub fn clean_tags(meta: &mut VorbisComment, fold_case: bool) {
+    meta.comments = std::mem::take(&mut meta.comments)
+        .into_iter()
         .map(|(k, v)| {
-            (
-                k.to_owned(),
-                v.iter().map(|s| s.to_owned()).unique().collect::<Vec<_>>(),
-            )
+            let v = v
+                .iter()
+                .map(|s| normalize_value(s))
+                .filter(|s| !s.is_empty())
+                .unique_by(|s| match fold_case {
+                    true => s.to_lowercase(),
+                    false => s.to_owned(),
+                })
+                .collect::<Vec<_>>();
+            (k, v)
         })
-        .collect::<Vec<_>>();
-    new_values.into_iter().for_each(|(k, v)| {
-        meta.set(k, v);
-    });
+        .filter(|(_, v)| !v.is_empty())
+        .collect();
+}
+
+/// Normalizes the invisible and typographic differences between values, so they can be compared.
+///
+/// Converts the value to Unicode NFC, removes zero-width and control characters (other than
+/// newlines), replaces curly quotes and dashes with their ASCII equivalents, and collapses
+/// repeated spaces, trimming each line and the value.
+pub fn normalize_value(value: &str) -> String {
+    let value = value
+        .nfc()
+        .filter_map(|c| match c {
+            '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}' | '\u{2032}' => Some('\''),
+            '\u{201C}' | '\u{201D}' | '\u{201E}' | '\u{201F}' | '\u{2033}' => Some('"'),
+            '\u{2010}'..='\u{2015}' | '\u{2212}' => Some('-'),
+            '\u{200B}'..='\u{200D}' | '\u{2060}' | '\u{FEFF}' => None,
+            '\n' => Some('\n'),
+            c if c.is_control() && c != '\t' => None,
+            c if c.is_whitespace() => Some(' '),
+            c => Some(c),
+        })
+        .collect::<String>();
+
+    value
+        .lines()
+        .map(|l| l.split(' ').filter(|w| !w.is_empty()).join(" "))
+        .join("\n")
+        .trim()
+        .to_string()
 }
 
-/// Deletes all non-standard tags.
-pub fn clean_non_standard_tags(meta: &mut VorbisComment) {
+/// Deletes all non-standard tags, other than those matching a pattern to keep. Returns the removed
+/// tags and their values.
+pub fn clean_non_standard_tags(
+    meta: &mut VorbisComment,
+    keep: &[Regex],
+) -> BTreeMap<String, Vec<String>> {
     let bad_tags = meta
         .comments
         .keys()
         .filter(|k| FlacTags::from_str(k.as_str()).is_err())
+        .filter(|k| !keep.iter().any(|r| r.is_match(k)))
         .map(|t| t.to_owned())
         .collect::<Vec<_>>();
 
-    bad_tags.into_iter().for_each(|t| meta.remove(&t))
+    bad_tags
+        .into_iter()
+        .filter_map(|t| meta.comments.remove(&t).map(|v| (t, v)))
+        .collect()
 }
 
 /// Print a newline seperate list of files, ready for piping into other programs.
@@ -74,12 +429,89 @@ pub fn list_detailed(path: &Path, tag: &VorbisComment) {
     });
 }
 
+/// Prints an assignment skipped for a file, as a tag couldn't be read as its type, to standard
+/// error.
+pub fn print_skipped(path: &Path, error: &QueryEvalError) {
+    eprintln!(
+        "{:6} {} skipped assignment, {}",
+        "File:".bold(),
+        path.to_str().unwrap().green(),
+        error.to_string().yellow()
+    );
+}
+
+/// Prints the tags removed from a file by cleaning, to standard error.
+pub fn print_removed(path: &Path, removed: &BTreeMap<String, Vec<String>>) {
+    if removed.is_empty() {
+        return;
+    }
+
+    eprintln!("{:6} {}", "File:".bold(), path.to_str().unwrap().green());
+    eprintln!("{}", "Removed:".bold());
+    removed.iter().for_each(|(k, v)| {
+        eprintln!("{:5}{}{}:{:?}", "", "-".red(), k.as_str().red(), v);
+    });
+}
+
+/// Prints the tags (and pictures) that differ between the file's original and edited tags, for
+/// previewing a dry run. Files without any changes are skipped.
+pub fn print_changes(
+    path: &Path,
+    before: &VorbisComment,
+    after: &VorbisComment,
+    pictures: (&[&Picture], &[&Picture]),
+) {
+    let changed = before
+        .comments
+        .keys()
+        .chain(after.comments.keys())
+        .unique()
+        .sorted()
+        .filter(|k| before.comments.get(*k) != after.comments.get(*k))
+        .collect::<Vec<_>>();
+    let pictures_changed = pictures.0 != pictures.1;
+    if changed.is_empty() && !pictures_changed {
+        return;
+    }
+
+    println!("{:6} {}", "File:".bold(), path.to_str().unwrap().green());
+    println!("{}", "Changes:".bold());
+    changed.into_iter().for_each(|k| {
+        if let Some(v) = before.comments.get(k) {
+            println!("{:5}{}{}:{:?}", "", "-".red(), k.as_str().red(), v);
+        }
+        if let Some(v) = after.comments.get(k) {
+            println!("{:5}{}{}:{:?}", "", "+".green(), k.as_str().green(), v);
+        }
+    });
+    if pictures_changed {
+        let describe = |p: &&Picture| {
+            format!(
+                "{:?} {} {}x{} ({} bytes)",
+                p.picture_type,
+                p.mime_type,
+                p.width,
+                p.height,
+                p.data.len()
+            )
+        };
+        let (before, after) = pictures;
+        before.iter().map(describe).for_each(|p| {
+            println!("{:5}{}{}:{:?}", "", "-".red(), "PICTURE".red(), p);
+        });
+        after.iter().map(describe).for_each(|p| {
+            println!("{:5}{}{}:{:?}", "", "+".green(), "PICTURE".green(), p);
+        });
+    }
+}
+
 #[cfg(test)]
 mod test {
     use metaflac::block::VorbisComment;
 
     use super::*;
-    use crate::tags::FlacTags;
+    use crate::case::{CaseStyle, Language, CASE_EXCEPTIONS};
+    use crate::tags::{parse_keep_args, FlacTags};
 
     #[test]
     fn test_set_tags() {
@@ -132,7 +564,7 @@ mod test {
 
         actual.set("TITLE", vec!["Foo", "Foo"]);
         actual.set("ARTIST", vec!["Eksheke"]);
-        clean_tags(&mut actual);
+        clean_tags(&mut actual, false);
 
         expected.set("TITLE", vec!["Foo"]);
         expected.set("ARTIST", vec!["Eksheke"]);
@@ -140,6 +572,44 @@ mod test {
         assert_eq!(actual, expected)
     }
 
+    #[test]
+    fn test_clean_tags_normalized() {
+        let mut actual = VorbisComment::new();
+        let mut expected = VorbisComment::new();
+
+        actual.set("TITLE", vec!["Don\u{2019}t  Stop ", "Don't Stop\u{200B}"]);
+        actual.set("ARTIST", vec!["Beyonce\u{301}", "Beyoncé", "\t\u{FEFF}"]);
+        actual.set("COMMENT", vec![" "]);
+        actual.set(
+            "LYRICS",
+            vec!["Line \u{201C}one\u{201D}  \r\n\nLine\u{A0}two \u{2013}"],
+        );
+        clean_tags(&mut actual, false);
+
+        expected.set("TITLE", vec!["Don't Stop"]);
+        expected.set("ARTIST", vec!["Beyoncé"]);
+        expected.set("LYRICS", vec!["Line \"one\"\n\nLine two -"]);
+
+        assert_eq!(actual, expected)
+    }
+
+    #[test]
+    fn test_clean_tags_case() {
+        let mut actual = VorbisComment::new();
+        let mut expected = VorbisComment::new();
+
+        actual.set("GENRE", vec!["Rock", "Jazz", "rock", "JAZZ "]);
+        clean_tags(&mut actual, false);
+        expected.set("GENRE", vec!["Rock", "Jazz", "rock", "JAZZ"]);
+
+        assert_eq!(actual, expected);
+
+        clean_tags(&mut actual, true);
+        expected.set("GENRE", vec!["Rock", "Jazz"]);
+
+        assert_eq!(actual, expected)
+    }
+
     #[test]
     fn test_clean_non_standard_tags() {
         let mut actual = VorbisComment::new();
@@ -148,12 +618,31 @@ mod test {
         actual.set("TITLE", vec!["Foo"]);
         actual.set("EKSHEKE", vec!["Foo", "Foo"]);
         actual.set("ARTIST", vec!["Eksheke"]);
-        clean_non_standard_tags(&mut actual);
+        actual.set("ALBUMARTIST", vec!["Eksheke"]);
+        actual.set(
+            "MUSICBRAINZ_TRACKID",
+            vec!["0e1a7a4a-6f0e-4c1a-a1a4-4b0e6b6f0e1a"],
+        );
+        actual.set("REPLAYGAIN_TRACK_GAIN", vec!["-6.5 dB"]);
+        let removed = clean_non_standard_tags(&mut actual, &parse_keep_args(&["replaygain_*"]));
 
         expected.set("TITLE", vec!["Foo"]);
+        expected.set("REPLAYGAIN_TRACK_GAIN", vec!["-6.5 dB"]);
         expected.set("ARTIST", vec!["Eksheke"]);
-
-        assert_eq!(actual, expected)
+        expected.set("ALBUMARTIST", vec!["Eksheke"]);
+        expected.set(
+            "MUSICBRAINZ_TRACKID",
+            vec!["0e1a7a4a-6f0e-4c1a-a1a4-4b0e6b6f0e1a"],
+        );
+
+        assert_eq!(actual, expected);
+        assert_eq!(
+            removed,
+            BTreeMap::from([(
+                "EKSHEKE".to_string(),
+                vec!["Foo".to_string(), "Foo".to_string()]
+            )])
+        );
     }
 
     #[test]
@@ -169,4 +658,228 @@ mod test {
 
         assert_eq!(actual, expected)
     }
+
+    #[test]
+    fn test_edits() {
+        let mut actual = VorbisComment::new();
+        let mut expected = VorbisComment::new();
+
+        actual.set("TITLE", vec!["Foo"]);
+        actual.set("GENRE", vec!["Jazz"]);
+        actual.set("COMMENT", vec!["Ripped with EAC"]);
+        let original = actual.clone();
+        let template = |f: &str, v: &str| Edit::Set(f.to_string(), vec![v.to_string()]);
+        [
+            template("TITLE", "Bar ({title})").into_template().unwrap(),
+            template("VERSION", "{title}").into_template().unwrap(),
+            Edit::Set("ALBUM".to_string(), vec!["Song {Remix}".to_string()]),
+            Edit::Append("GENRE".to_string(), vec!["Hip-Hop".to_string()]),
+            Edit::Delete("COMMENT".to_string()),
+        ]
+        .iter()
+        .for_each(|e| e.apply(&mut actual, &original, Path::new("Foo.flac")));
+
+        expected.set("TITLE", vec!["Bar (Foo)"]);
+        expected.set("VERSION", vec!["Foo"]);
+        expected.set("ALBUM", vec!["Song {Remix}"]);
+        expected.set("GENRE", vec!["Jazz", "Hip-Hop"]);
+
+        assert_eq!(actual, expected)
+    }
+
+    #[test]
+    fn test_remove_value() {
+        let mut actual = VorbisComment::new();
+        let mut expected = VorbisComment::new();
+
+        actual.set("ARTIST", vec!["Nujabes", "Cise Starr", "Cise Starr"]);
+        actual.set("TITLE", vec!["Feather"]);
+        remove_value(&mut actual, FlacTags::Artist, "Cise Starr");
+        remove_value(&mut actual, FlacTags::Title, "Feather");
+
+        expected.set("ARTIST", vec!["Nujabes"]);
+
+        assert_eq!(actual, expected)
+    }
+
+    #[test]
+    fn test_insert_value() {
+        let mut actual = VorbisComment::new();
+        let mut expected = VorbisComment::new();
+
+        actual.set("ARTIST", vec!["Cise Starr"]);
+        insert_value(&mut actual, FlacTags::Artist, 1, "Nujabes".to_string());
+        insert_value(&mut actual, FlacTags::Artist, 10, "Akin".to_string());
+        insert_value(&mut actual, FlacTags::Genre, 2, "Jazz".to_string());
+
+        expected.set("ARTIST", vec!["Nujabes", "Cise Starr", "Akin"]);
+        expected.set("GENRE", vec!["Jazz"]);
+
+        assert_eq!(actual, expected)
+    }
+
+    #[test]
+    fn test_dedupe_sort_truncate_values() {
+        let mut actual = VorbisComment::new();
+        let mut expected = VorbisComment::new();
+
+        actual.set("GENRE", vec!["rock", "Jazz", "Rock", "ambient"]);
+        actual.set("ARTIST", vec!["Eksheke"]);
+        dedupe_values(&mut actual, FlacTags::Genre);
+        sort_values(&mut actual, FlacTags::Genre);
+        truncate_values(&mut actual, FlacTags::Genre, 2);
+        truncate_values(&mut actual, FlacTags::Artist, 0);
+
+        expected.set("GENRE", vec!["ambient", "Jazz"]);
+
+        assert_eq!(actual, expected)
+    }
+
+    #[test]
+    fn test_replace_values() {
+        let mut actual = VorbisComment::new();
+        let mut expected = VorbisComment::new();
+        let sub = |s: &str| Substitution::from_str(s).unwrap();
+
+        actual.set("TITLE", vec!["Feather (Remastered)"]);
+        actual.set("ALBUM", vec!["Modal Soul (Remastered)"]);
+        actual.set(
+            "ARTIST",
+            vec!["Nujabes feat. Cise Starr", "Akin Feat. Nujabes"],
+        );
+        replace_values(&mut actual, FlacTags::Title, &sub(r"s/ \(Remastered\)$//"));
+        replace_values(&mut actual, FlacTags::Artist, &sub(r"s/ feat\. .*//i"));
+        replace_values(&mut actual, "*", &sub("s/Modal/Metaphorical/"));
+
+        expected.set("TITLE", vec!["Feather"]);
+        expected.set("ALBUM", vec!["Metaphorical Soul (Remastered)"]);
+        expected.set("ARTIST", vec!["Nujabes", "Akin"]);
+
+        assert_eq!(actual, expected)
+    }
+
+    #[test]
+    fn test_rename_field() {
+        let mut actual = VorbisComment::new();
+        let mut expected = VorbisComment::new();
+
+        actual.set("YEAR", vec!["2005"]);
+        actual.set("DATE", vec!["2005"]);
+        actual.set("ALBUM ARTIST", vec!["Nujabes"]);
+        actual.set("ORGANISATION", vec!["Hydeout"]);
+        actual.set("ORGANIZATION", vec!["Hydeout Productions"]);
+        crate::tags::LEGACY_TAGS
+            .iter()
+            .for_each(|(old, new)| rename_field(&mut actual, old, new));
+
+        expected.set("DATE", vec!["2005"]);
+        expected.set("ALBUMARTIST", vec!["Nujabes"]);
+        expected.set("ORGANIZATION", vec!["Hydeout Productions", "Hydeout"]);
+
+        assert_eq!(actual, expected)
+    }
+
+    #[test]
+    fn test_tags_from_path() {
+        let mut actual = VorbisComment::new();
+        let mut expected = VorbisComment::new();
+        let pattern = PathPattern::from_str("%artist%/%album%/%tracknumber% %title%").unwrap();
+
+        actual.set("TITLE", vec!["Track 3"]);
+        actual.set("ARTIST", vec!["Nujabes"]);
+        let path = Path::new("Cise Starr/Modal Soul/03 Feather.flac");
+        tags_from_path(&mut actual, &pattern, path, false);
+        tags_from_path(&mut actual, &pattern, Path::new("03 Feather.flac"), false);
+
+        expected.set("TITLE", vec!["Feather"]);
+        expected.set("ARTIST", vec!["Cise Starr"]);
+        expected.set("ALBUM", vec!["Modal Soul"]);
+        expected.set("TRACKNUMBER", vec!["03"]);
+
+        assert_eq!(actual, expected)
+    }
+
+    #[test]
+    fn test_copy_move_tags() {
+        let mut actual = VorbisComment::new();
+        let mut expected = VorbisComment::new();
+
+        actual.set("ARTIST", vec!["Nujabes", "Cise Starr"]);
+        actual.set("ALBUMARTIST", vec!["Various Artists"]);
+        actual.set("COMMENT", vec!["Vinyl rip"]);
+        actual.set("DESCRIPTION", vec!["24-bit"]);
+        copy_tags(&mut actual, FlacTags::Artist, FlacTags::Albumartist, false);
+        move_tags(&mut actual, FlacTags::Comment, "DESCRIPTION", true);
+        move_tags(&mut actual, FlacTags::Genre, FlacTags::Title, false);
+        move_tags(&mut actual, FlacTags::Artist, FlacTags::Artist, false);
+
+        expected.set("ARTIST", vec!["Nujabes", "Cise Starr"]);
+        expected.set("ALBUMARTIST", vec!["Nujabes", "Cise Starr"]);
+        expected.set("DESCRIPTION", vec!["24-bit", "Vinyl rip"]);
+
+        assert_eq!(actual, expected)
+    }
+
+    #[test]
+    fn test_tags_from() {
+        let mut actual = VorbisComment::new();
+        let mut reference = VorbisComment::new();
+        let mut expected = VorbisComment::new();
+
+        actual.set("TITLE", vec!["Feather"]);
+        actual.set("ALBUM", vec!["Modal Sol"]);
+        actual.set("GENRE", vec!["Jazz"]);
+        reference.set("ALBUM", vec!["Modal Soul"]);
+        reference.set("GENRE", vec!["Hip-Hop"]);
+        tags_from(&mut actual, &reference, false);
+        reference.remove("ALBUM");
+        tags_from(&mut actual, &reference, true);
+
+        expected.set("TITLE", vec!["Feather"]);
+        expected.set("ALBUM", vec!["Modal Soul"]);
+        expected.set("GENRE", vec!["Hip-Hop", "Hip-Hop"]);
+
+        assert_eq!(actual, expected)
+    }
+
+    #[test]
+    fn test_split_join_values() {
+        let mut actual = VorbisComment::new();
+        let mut expected = VorbisComment::new();
+
+        actual.set(
+            "ARTIST",
+            vec!["Nujabes FEAT. Shing02; Cise Starr", "Uyama Hiroto / "],
+        );
+        actual.set("GENRE", vec!["Hip-Hop", "Jazz"]);
+        actual.set("COMPOSER", vec![";"]);
+        let delimiters = [";", " / ", " feat. "].map(String::from);
+        split_values(&mut actual, FlacTags::Artist, &delimiters);
+        split_values(&mut actual, FlacTags::Composer, &delimiters);
+        join_values(&mut actual, FlacTags::Genre, "; ");
+        join_values(&mut actual, FlacTags::Album, "; ");
+
+        expected.set(
+            "ARTIST",
+            vec!["Nujabes", "Shing02", "Cise Starr", "Uyama Hiroto"],
+        );
+        expected.set("GENRE", vec!["Hip-Hop; Jazz"]);
+
+        assert_eq!(actual, expected)
+    }
+
+    #[test]
+    fn test_change_case() {
+        let mut actual = VorbisComment::new();
+        let mut expected = VorbisComment::new();
+
+        actual.set("TITLE", vec!["LUV (SIC) PART 3 FEAT. SHING02", "the end"]);
+        let case = CaseTransform::new(CaseStyle::Title, Language::English, CASE_EXCEPTIONS);
+        change_case(&mut actual, FlacTags::Title, &case);
+        change_case(&mut actual, FlacTags::Artist, &case);
+
+        expected.set("TITLE", vec!["Luv (Sic) Part 3 feat. Shing02", "The End"]);
+
+        assert_eq!(actual, expected)
+    }
 }
@@ -1,6 +1,8 @@
+use crate::schema::{FieldType, Schema, EMPTY_SCHEMA};
 use crate::tags::FlacTags;
 use itertools::Itertools;
 use metaflac::block::VorbisComment;
+use metaflac::Tag;
 use pest::pratt_parser::PrattParser;
 use pest::Parser;
 use pest::{iterators::Pairs, RuleType};
@@ -17,6 +19,13 @@ use thiserror::Error;
 
 use crate::PRATT_PARSER;
 
+mod properties;
+pub use properties::{Properties, Property};
+
+lazy_static::lazy_static! {
+    static ref NO_COMMENTS: VorbisComment = VorbisComment::new();
+}
+
 #[allow(clippy::enum_variant_names)]
 #[derive(Error, Debug)]
 pub enum QueryParseError {
@@ -28,22 +37,30 @@ pub enum QueryParseError {
     PrefixError,
     #[error("`{0}` could not be parsed as u32")]
     IntegerError(String),
+    #[error("`{0}` could not be parsed as a number")]
+    NumberError(String),
     #[error("Syntax of query is invalid")]
     SyntaxError,
     #[error("Invalid date format: {0}")]
     InvalidDate(String),
+    #[error("Aggregate `{0}` requires a tag, e.g. `{0}(Album)`")]
+    AggregateError(String),
+    #[error("Function `{0}` takes {1} argument(s)")]
+    FunctionError(String, usize),
 }
 
 #[derive(Error, Debug)]
 pub enum QueryEvalError {
     #[error("Cannot apply logic NOT (!) to type: {0}")]
     InvalidNot(String),
-    #[error("Mismatching types cannot be compared: `{0}` and `{0}`")]
+    #[error("Mismatching types cannot be compared: `{0}` and `{1}`")]
     MismatchingTypes(String, String),
     #[error("Invalid String operation: `{0}`")]
     StringOperation(String),
     #[error("Invalid Integer operation: `{0}`")]
     IntegerOperation(String),
+    #[error("Invalid Number operation: `{0}`")]
+    NumberOperation(String),
     #[error("Invalid Date operation: `{0}`")]
     DateOperation(String),
     #[error("Invalid Boolean operation: `{0}`")]
@@ -52,17 +69,241 @@ pub enum QueryEvalError {
     BadEvaluation,
     #[error("Tag cannot be compared as it is not set: {0}")]
     TagNotSet(String),
+    #[error("Picture cannot be compared as the file has no front cover: {0}")]
+    NoFrontCover(String),
 }
 
 #[derive(pest_derive::Parser)]
 #[grammar = "query/query.pest"]
 pub struct QueryParser;
 impl QueryParser {
-    pub fn parse_grammer(s: &str) -> Result<Pairs<Rule>, QueryParseError> {
+    pub fn parse_grammer(s: &str) -> Result<Pairs<'_, Rule>, QueryParseError> {
         QueryParser::parse(Rule::expr, s).map_err(|_| QueryParseError::SyntaxError)
     }
 }
 
+/// A complete query, optionally partitioning the files into groups that share the value of a tag.
+///
+/// Aggregates (e.g. `count()`) are evaluated over the group of the file being tested, or over
+/// every file when no grouping is given.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    pub expr: Expr,
+    pub group_by: Option<FlacTags>,
+}
+impl FromStr for Query {
+    type Err = QueryParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let pairs = QueryParser::parse(Rule::query, s).map_err(|_| QueryParseError::SyntaxError)?;
+
+        let mut expr = None;
+        let mut group_by = None;
+        for p in pairs {
+            match p.as_rule() {
+                Rule::expr => expr = Some(build(p.into_inner())?),
+                Rule::group_by | Rule::within => {
+                    let tag = p
+                        .into_inner()
+                        .find(|p| p.as_rule() == Rule::tag)
+                        .expect("Grouping tag validated by pest grammar already");
+                    group_by = Some(
+                        FlacTags::from_str(tag.as_str())
+                            .expect("Tag validated by pest grammar already"),
+                    );
+                }
+                _ => (),
+            }
+        }
+
+        Ok(Query {
+            expr: expr.ok_or(QueryParseError::SyntaxError)?,
+            group_by,
+        })
+    }
+}
+impl Query {
+    /// Evaluates the query against every file, returning whether each file satisfies the query.
+    /// Files without a front cover don't satisfy queries of the front cover's properties.
+    pub fn eval_all(
+        &self,
+        files: &[Metadata],
+        options: EvalOptions<'_>,
+    ) -> Result<Vec<bool>, QueryEvalError> {
+        self.eval_each(files, options).into_iter().collect()
+    }
+
+    /// Evaluates the query against every file as with [`Query::eval_all`], where files without a
+    /// tag the query refers to don't satisfy the query.
+    pub fn eval_all_set(
+        &self,
+        files: &[Metadata],
+        options: EvalOptions<'_>,
+    ) -> Result<Vec<bool>, QueryEvalError> {
+        self.eval_each(files, options)
+            .into_iter()
+            .map(|r| match r {
+                Err(QueryEvalError::TagNotSet(_)) => Ok(false),
+                r => r,
+            })
+            .collect()
+    }
+
+    /// Whether the query is evaluated over groups of files, either explicitly grouped or with
+    /// aggregates over every file, rather than over each file on its own.
+    pub fn is_grouped(&self) -> bool {
+        self.group_by.is_some() || self.expr.has_aggregate()
+    }
+
+    fn eval_each(
+        &self,
+        files: &[Metadata],
+        options: EvalOptions<'_>,
+    ) -> Vec<Result<bool, QueryEvalError>> {
+        // files without the grouping tag are each in a group of their own
+        let groups = (0..files.len()).into_group_map_by(|&i| match self.group_by {
+            Some(t) => match files[i].comments.get(t.as_str()) {
+                Some(values) => (Some(values.to_owned()), None),
+                None => (None, Some(i)),
+            },
+            None => (None, None),
+        });
+
+        let mut results = (0..files.len()).map(|_| Ok(false)).collect::<Vec<_>>();
+        for members in groups.into_values() {
+            let group = members
+                .iter()
+                .map(|&i| &files[i].comments)
+                .collect::<Vec<_>>();
+            for &i in members.iter() {
+                let env = Env {
+                    comments: &files[i].comments,
+                    properties: Some(&files[i].properties),
+                    group: Some(&group),
+                    options,
+                };
+                results[i] = match self.expr.eval_in(&env) {
+                    Ok(Value::Boolean(b)) => Ok(b),
+                    Ok(_) => Err(QueryEvalError::BadEvaluation),
+                    Err(QueryEvalError::NoFrontCover(_)) => Ok(false),
+                    Err(e) => Err(e),
+                };
+            }
+        }
+
+        results
+    }
+}
+
+/// A tag set to the value of an expression, optionally only when a condition holds, e.g.
+/// `Album = upper(Album) if Genre == "Classical"`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Assignment {
+    pub field: String,
+    pub value: Expr,
+    pub condition: Option<Expr>,
+}
+
+/// Assignments separated by `;`, applied to each file in order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Assignments(pub Vec<Assignment>);
+impl FromStr for Assignments {
+    type Err = QueryParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let pairs =
+            QueryParser::parse(Rule::assignments, s).map_err(|_| QueryParseError::SyntaxError)?;
+
+        pairs
+            .filter(|p| p.as_rule() == Rule::assignment)
+            .map(|p| {
+                let mut inner = p.into_inner();
+                let field = inner
+                    .next()
+                    .expect("Assignment target validated by pest grammar already");
+                let value = inner
+                    .next()
+                    .expect("Assignment value validated by pest grammar already");
+                let condition = inner.next().and_then(|c| c.into_inner().next());
+
+                Ok(Assignment {
+                    field: match field.as_rule() {
+                        Rule::tag => FlacTags::from_str(field.as_str())
+                            .expect("Tag validated by pest grammar already")
+                            .as_str()
+                            .to_string(),
+                        _ => field.as_str().to_uppercase(),
+                    },
+                    value: build(value.into_inner())?,
+                    condition: condition.map(|c| build(c.into_inner())).transpose()?,
+                })
+            })
+            .collect::<Result<_, _>>()
+            .map(Assignments)
+    }
+}
+impl Assignments {
+    /// Applies each assignment to a file's tags in turn, so later assignments see the values set
+    /// by earlier ones. Assignments referring to a tag that is not set (or the front cover of a file
+    /// without one) are skipped, and assignments evaluating to no values delete the tag. An
+    /// assignment of just a tag copies its values as they are written.
+    ///
+    /// Assignments referring to a tag that can't be read as its type are also skipped, returning
+    /// their errors so they can be reported.
+    pub fn apply(
+        &self,
+        tag: &mut Tag,
+        options: EvalOptions<'_>,
+    ) -> Result<Vec<QueryEvalError>, QueryEvalError> {
+        let mut skipped = Vec::new();
+        let properties = Properties::read(Some(tag));
+        for assignment in &self.0 {
+            let env = Env {
+                comments: tag.vorbis_comments().unwrap_or(&NO_COMMENTS),
+                properties: Some(&properties),
+                group: None,
+                options,
+            };
+            let applies = match &assignment.condition {
+                Some(condition) => condition.eval_in(&env),
+                None => Ok(Value::Boolean(true)),
+            };
+            let value = match applies {
+                Ok(Value::Boolean(true)) => match assignment.value.field() {
+                    Some(field) => env
+                        .comments
+                        .get(field)
+                        .map(|values| Value::String(values.to_owned()))
+                        .ok_or(QueryEvalError::TagNotSet(field.to_string())),
+                    None => assignment.value.eval_in(&env),
+                },
+                Ok(Value::Boolean(false)) => continue,
+                Ok(_) => Err(QueryEvalError::BadEvaluation)?,
+                Err(e) => Err(e),
+            };
+
+            let meta = tag.vorbis_comments_mut();
+            match value.map(Value::into_strings) {
+                Ok(values) if values.is_empty() => meta.remove(&assignment.field),
+                Ok(values) => meta.set(assignment.field.as_str(), values),
+                Err(QueryEvalError::TagNotSet(_) | QueryEvalError::NoFrontCover(_)) => continue,
+                Err(
+                    e @ (QueryEvalError::IntegerOperation(_)
+                    | QueryEvalError::NumberOperation(_)
+                    | QueryEvalError::DateOperation(_)
+                    | QueryEvalError::BooleanOperation(_)),
+                ) => skipped.push(e),
+                Err(e) => Err(e)?,
+            }
+        }
+
+        Ok(skipped)
+    }
+
+    /// The tags set by the assignments.
+    pub fn fields(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().map(|a| a.field.as_str())
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BinaryOperator {
     Equals,
@@ -76,7 +317,22 @@ pub enum BinaryOperator {
     Or,
 }
 impl BinaryOperator {
-    fn eval(&self, lhs: Value, rhs: Value) -> Result<bool, QueryEvalError> {
+    fn eval(&self, lhs: Value, rhs: Value, options: EvalOptions) -> Result<bool, QueryEvalError> {
+        let (lhs, rhs) = if options.strict {
+            (lhs, rhs)
+        } else {
+            Value::coerce(lhs, rhs)
+        };
+        let (lhs, rhs) = match (lhs, rhs) {
+            (Value::Date(a), Value::Date(b)) if !options.strict => {
+                let (a, b) = Date::truncate(a, b);
+                (Value::Date(a), Value::Date(b))
+            }
+            (Value::Integer(a), Value::Number(b)) => (Value::Number(a as f64), Value::Number(b)),
+            (Value::Number(a), Value::Integer(b)) => (Value::Number(a), Value::Number(b as f64)),
+            x => x,
+        };
+
         Ok(match (lhs, self, rhs) {
             // Strings
             (Value::String(a), Self::Equals, Value::String(b)) => {
@@ -93,6 +349,8 @@ impl BinaryOperator {
             // Boolean
             (Value::Boolean(a), Self::Equals, Value::Boolean(b)) => a == b,
             (Value::Boolean(a), Self::NotEquals, Value::Boolean(b)) => a != b,
+            (Value::Boolean(a), Self::And, Value::Boolean(b)) => a && b,
+            (Value::Boolean(a), Self::Or, Value::Boolean(b)) => a || b,
             (Value::Boolean(a), op, Value::Boolean(b)) => {
                 Err(QueryEvalError::BooleanOperation(format!("{op:?}")))?
             }
@@ -116,6 +374,16 @@ impl BinaryOperator {
             (Value::Integer(a), op, Value::Integer(b)) => {
                 Err(QueryEvalError::IntegerOperation(format!("{op:?}")))?
             }
+            // Number
+            (Value::Number(a), Self::Equals, Value::Number(b)) => a == b,
+            (Value::Number(a), Self::NotEquals, Value::Number(b)) => a != b,
+            (Value::Number(a), Self::Greater, Value::Number(b)) => a > b,
+            (Value::Number(a), Self::GreaterEq, Value::Number(b)) => a >= b,
+            (Value::Number(a), Self::Less, Value::Number(b)) => a < b,
+            (Value::Number(a), Self::LessEq, Value::Number(b)) => a <= b,
+            (Value::Number(a), op, Value::Number(b)) => {
+                Err(QueryEvalError::NumberOperation(format!("{op:?}")))?
+            }
             // Type mistmatch
             (a, _, b) => Err(QueryEvalError::MismatchingTypes(
                 format!("{a:?}"),
@@ -135,15 +403,193 @@ pub enum Expr {
     },
     Not(Box<Expr>),
     Value(Value),
+    Aggregate(Aggregate, Option<FlacTags>),
+    Call(Function, Vec<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Function {
+    /// Removes surrounding whitespace from each value.
+    Trim,
+    /// Converts each value to uppercase.
+    Upper,
+    /// Converts each value to lowercase.
+    Lower,
+}
+impl Function {
+    fn arity(&self) -> usize {
+        match self {
+            Self::Trim | Self::Upper | Self::Lower => 1,
+        }
+    }
+
+    fn eval(&self, args: Vec<Value>) -> Value {
+        let values = args.into_iter().flat_map(Value::into_strings);
+        Value::String(match self {
+            Self::Trim => values.map(|s| s.trim().to_string()).collect(),
+            Self::Upper => values.map(|s| s.to_uppercase()).collect(),
+            Self::Lower => values.map(|s| s.to_lowercase()).collect(),
+        })
+    }
+}
+impl FromStr for Function {
+    type Err = QueryParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.to_lowercase().as_str() {
+            "trim" => Self::Trim,
+            "upper" => Self::Upper,
+            "lower" => Self::Lower,
+            _ => Err(QueryParseError::AtomError(s.to_string()))?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aggregate {
+    /// Number of files in the group, or the number of files with the given tag set.
+    Count,
+    /// Number of distinct values of the given tag within the group.
+    Distinct,
 }
+impl Aggregate {
+    fn eval(&self, tag: Option<FlacTags>, group: &[&VorbisComment]) -> u32 {
+        let values = group
+            .iter()
+            .filter_map(|c| match tag {
+                Some(t) => c.get(t.as_str()),
+                None => Some(&EMPTY),
+            })
+            .collect::<Vec<_>>();
+
+        (match self {
+            Self::Count => values.len(),
+            Self::Distinct => values.into_iter().unique().count(),
+        }) as u32
+    }
+}
+
+static EMPTY: Vec<String> = Vec::new();
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// What a query reads of a file; its comments, and the properties of its other metadata blocks.
+#[derive(Debug, Clone)]
+pub struct Metadata {
+    pub comments: VorbisComment,
+    pub properties: Properties,
+}
+impl From<&Tag> for Metadata {
+    fn from(tag: &Tag) -> Self {
+        Metadata {
+            comments: tag.vorbis_comments().cloned().unwrap_or_default(),
+            properties: Properties::read(Some(tag)),
+        }
+    }
+}
+
+/// The environment a query is evaluated in; the file being tested and the group it belongs to.
+#[derive(Clone, Copy)]
+pub struct Env<'a> {
+    pub comments: &'a VorbisComment,
+    /// The file's properties that are not tags. `None` when only the comments are known.
+    pub properties: Option<&'a Properties>,
+    /// Files sharing the grouping tag with this file (including itself). `None` when the file is
+    /// evaluated on its own.
+    pub group: Option<&'a [&'a VorbisComment]>,
+    pub options: EvalOptions<'a>,
+}
+
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub enum Value {
     Boolean(bool),
     Date(Date),
     Integer(u32),
+    Number(f64),
     String(Vec<String>),
     Tag(FlacTags),
+    /// A non-standard tag, stored by its uppercase field name.
+    Field(String),
+    Property(Property),
+}
+impl Value {
+    /// Coerces a string operand to match the type of the other operand, if the first string value
+    /// can be read as that type (see [`coerce_integer`] and [`coerce_date`]).
+    fn coerce(lhs: Value, rhs: Value) -> (Value, Value) {
+        fn convert(s: &[String], other: &Value) -> Option<Value> {
+            let s = s.first()?;
+            match other {
+                Value::Integer(_) => coerce_integer(s).map(Value::Integer),
+                Value::Number(_) => coerce_number(s).map(Value::Number),
+                Value::Date(_) => coerce_date(s).map(Value::Date),
+                Value::Boolean(_) => coerce_boolean(s).map(Value::Boolean),
+                _ => None,
+            }
+        }
+
+        match (&lhs, &rhs) {
+            (Value::String(a), b) => (convert(a, b).unwrap_or(lhs), rhs),
+            (a, Value::String(b)) => {
+                let rhs = convert(b, a).unwrap_or(rhs);
+                (lhs, rhs)
+            }
+            _ => (lhs, rhs),
+        }
+    }
+
+    /// Converts an evaluated value to tag values.
+    pub fn into_strings(self) -> Vec<String> {
+        match self {
+            Value::Boolean(b) => vec![b.to_string()],
+            Value::Date(d) => vec![d.to_string()],
+            Value::Integer(i) => vec![i.to_string()],
+            Value::Number(n) => vec![n.to_string()],
+            Value::String(s) => s,
+            Value::Tag(t) => vec![t.as_str().to_string()],
+            Value::Field(f) => vec![f],
+            Value::Property(p) => vec![p.as_str().to_string()],
+        }
+    }
+}
+
+/// Leniently reads a tag value as an integer, ignoring surrounding whitespace, leading zeros, and
+/// any total following a `/` (e.g. ` 03`, or `3/12` for track 3 of 12).
+pub fn coerce_integer(s: &str) -> Option<u32> {
+    s.trim().split('/').next()?.trim().parse().ok()
+}
+
+/// Leniently reads a tag value as a number, ignoring surrounding whitespace.
+pub fn coerce_number(s: &str) -> Option<f64> {
+    s.trim().parse().ok()
+}
+
+/// Leniently reads a tag value as a date, ignoring surrounding whitespace.
+pub fn coerce_date(s: &str) -> Option<Date> {
+    Date::from_str(s.trim()).ok()
+}
+
+/// Leniently reads a tag value as a boolean, accepting `true`/`false`, `yes`/`no`, `on`/`off`, and
+/// `1`/`0` in any case.
+pub fn coerce_boolean(s: &str) -> Option<bool> {
+    match s.trim().to_lowercase().as_str() {
+        "true" | "yes" | "y" | "on" | "1" => Some(true),
+        "false" | "no" | "n" | "off" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+/// Options controlling how tag values are interpreted during evaluation.
+#[derive(Debug, Clone, Copy)]
+pub struct EvalOptions<'a> {
+    /// Disables type coercion, so tags are only compared as the type they are stored as.
+    pub strict: bool,
+    /// Types declared for tags, determining what type each tag is read as.
+    pub schema: &'a Schema,
+}
+impl Default for EvalOptions<'_> {
+    fn default() -> Self {
+        EvalOptions {
+            strict: false,
+            schema: &EMPTY_SCHEMA,
+        }
+    }
 }
 
 pub fn build(pairs: Pairs<Rule>) -> Result<Expr, QueryParseError> {
@@ -158,6 +604,14 @@ pub fn build(pairs: Pairs<Rule>) -> Result<Expr, QueryParseError> {
                     .strip_suffix('\"')
                     .unwrap()
                     .to_string()])),
+                Rule::number => Expr::Value(Value::Number(
+                    p.as_str()
+                        .parse()
+                        .map_err(|_| QueryParseError::NumberError(p.as_str().into()))?,
+                )),
+                Rule::boolean => {
+                    Expr::Value(Value::Boolean(p.as_str().eq_ignore_ascii_case("true")))
+                }
                 Rule::integer => Expr::Value(Value::Integer(
                     p.as_str()
                         .parse()
@@ -166,6 +620,45 @@ pub fn build(pairs: Pairs<Rule>) -> Result<Expr, QueryParseError> {
                 Rule::tag => Expr::Value(Value::Tag(
                     FlacTags::from_str(p.as_str()).expect("Tag validated by pest grammar already"),
                 )),
+                Rule::property => Expr::Value(Value::Property(
+                    Property::from_str(p.as_str())
+                        .expect("Property validated by pest grammar already"),
+                )),
+                Rule::field => Expr::Value(Value::Field(p.as_str().to_uppercase())),
+                Rule::aggregate => {
+                    let mut inner = p.into_inner();
+                    let function = inner
+                        .next()
+                        .expect("Aggregate function validated by pest grammar already");
+                    let tag = inner.next().map(|t| {
+                        FlacTags::from_str(t.as_str())
+                            .expect("Tag validated by pest grammar already")
+                    });
+                    match (function.as_rule(), tag) {
+                        (Rule::count, tag) => Expr::Aggregate(Aggregate::Count, tag),
+                        (Rule::distinct, Some(tag)) => {
+                            Expr::Aggregate(Aggregate::Distinct, Some(tag))
+                        }
+                        _ => Err(QueryParseError::AggregateError(function.as_str().into()))?,
+                    }
+                }
+                Rule::call => {
+                    let mut inner = p.into_inner();
+                    let name = inner
+                        .next()
+                        .expect("Function validated by pest grammar already");
+                    let function = Function::from_str(name.as_str())?;
+                    let args = inner
+                        .map(|a| build(a.into_inner()))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    if args.len() != function.arity() {
+                        Err(QueryParseError::FunctionError(
+                            name.as_str().into(),
+                            function.arity(),
+                        ))?
+                    }
+                    Expr::Call(function, args)
+                }
                 Rule::expr => build(p.into_inner())?,
                 _ => Err(QueryParseError::AtomError(p.as_str().into()))?,
             })
@@ -199,60 +692,151 @@ pub fn build(pairs: Pairs<Rule>) -> Result<Expr, QueryParseError> {
         .parse(pairs)
 }
 
+impl Env<'_> {
+    /// Reads a tag as the type declared for it in the schema. Non-string types are read from the
+    /// first value of the tag.
+    fn lookup(&self, field: &str, name: String) -> Result<Value, QueryEvalError> {
+        let values = self
+            .comments
+            .get(field)
+            .ok_or(QueryEvalError::TagNotSet(name.clone()))?;
+
+        Ok(match self.options.schema.field_type(field) {
+            FieldType::String => Value::String(values.to_owned()),
+            t => {
+                let s = values
+                    .first()
+                    .ok_or(QueryEvalError::TagNotSet(name.clone()))?;
+                t.read(s, self.options.strict).ok_or(match t {
+                    FieldType::Date => QueryEvalError::DateOperation(name),
+                    FieldType::Number => QueryEvalError::NumberOperation(name),
+                    FieldType::Boolean => QueryEvalError::BooleanOperation(name),
+                    _ => QueryEvalError::IntegerOperation(name),
+                })?
+            }
+        })
+    }
+}
+
 impl Expr {
+    /// The tag the expression refers to, if it is just a tag.
+    fn field(&self) -> Option<&str> {
+        match self {
+            Expr::Value(Value::Tag(t)) => Some(t.as_str()),
+            Expr::Value(Value::Field(f)) => Some(f),
+            _ => None,
+        }
+    }
+
     pub fn eval(&self, env: &VorbisComment) -> Result<Value, QueryEvalError> {
+        self.eval_in(&Env {
+            comments: env,
+            properties: None,
+            group: None,
+            options: EvalOptions::default(),
+        })
+    }
+
+    fn has_aggregate(&self) -> bool {
+        match self {
+            Self::BinOp { lhs, rhs, .. } => lhs.has_aggregate() || rhs.has_aggregate(),
+            Self::Not(a) => a.has_aggregate(),
+            Self::Call(_, args) => args.iter().any(Expr::has_aggregate),
+            Self::Aggregate(..) => true,
+            Self::Value(_) => false,
+        }
+    }
+
+    pub fn eval_in(&self, env: &Env) -> Result<Value, QueryEvalError> {
         Ok(match self {
             Self::BinOp { lhs, op, rhs } => {
-                Value::Boolean(op.eval(lhs.eval(env)?, rhs.eval(env)?)?)
+                Value::Boolean(op.eval(lhs.eval_in(env)?, rhs.eval_in(env)?, env.options)?)
             }
+            Self::Aggregate(function, tag) => Value::Integer(match env.group {
+                Some(group) => function.eval(*tag, group),
+                None => function.eval(*tag, &[env.comments]),
+            }),
+            Self::Call(function, args) => function.eval(
+                args.iter()
+                    .map(|a| a.eval_in(env))
+                    .collect::<Result<_, _>>()?,
+            ),
             Self::Not(a) => {
-                let v = a.eval(env)?;
+                let v = a.eval_in(env)?;
                 match v {
-                    Value::Boolean(b) => v,
+                    Value::Boolean(b) => Value::Boolean(!b),
                     x => Err(QueryEvalError::InvalidNot(format!("{x:?}")))?,
                 }
             }
             Self::Value(v) => match v {
-                Value::Tag(t) => match t {
-                    FlacTags::Date => Value::Date(
-                        Date::from_str(
-                            env.get(t.as_str())
-                                .ok_or(QueryEvalError::TagNotSet(format!("{t:?}")))?
-                                .iter()
-                                .next()
-                                .ok_or(QueryEvalError::TagNotSet(format!("{t:?}")))?
-                                .as_str(),
-                        )
-                        .map_err(|_| QueryEvalError::DateOperation(format!("{t:?}")))?,
-                    ),
-                    FlacTags::Tracknumber => Value::Integer(
-                        env.get(t.as_str())
-                            .ok_or(QueryEvalError::TagNotSet(format!("{t:?}")))?
-                            .iter()
-                            .next()
-                            .ok_or(QueryEvalError::TagNotSet(format!("{t:?}")))?
-                            .as_str()
-                            .parse()
-                            .map_err(|_| QueryEvalError::IntegerOperation(format!("{t:?}")))?,
-                    ),
-                    _ => Value::String(
-                        env.get(t.as_str())
-                            .map(|v| v.to_owned())
-                            .ok_or(QueryEvalError::TagNotSet(format!("{t:?}")))?,
-                    ),
-                },
+                Value::Tag(t) => env.lookup(t.as_str(), format!("{t:?}"))?,
+                Value::Field(f) => env.lookup(f, f.to_owned())?,
+                Value::Property(p) => p.eval(env)?,
                 _ => v.clone(),
             },
         })
     }
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum Date {
     Year(u32),
     YearMonth(u32, u8),
     YearMonthDay(u32, u8, u8),
 }
+impl Date {
+    fn precision(&self) -> u8 {
+        match self {
+            Date::Year(_) => 0,
+            Date::YearMonth(_, _) => 1,
+            Date::YearMonthDay(_, _, _) => 2,
+        }
+    }
+
+    fn with_precision(self, precision: u8) -> Self {
+        match (self, precision) {
+            (Date::YearMonth(y, _), 0) | (Date::YearMonthDay(y, _, _), 0) => Date::Year(y),
+            (Date::YearMonthDay(y, m, _), 1) => Date::YearMonth(y, m),
+            (date, _) => date,
+        }
+    }
+
+    /// Truncates the more specific of the two dates to match the less specific date.
+    pub fn truncate(a: Date, b: Date) -> (Date, Date) {
+        let precision = a.precision().min(b.precision());
+        (a.with_precision(precision), b.with_precision(precision))
+    }
+
+    /// The year, month, and day, where an unknown month or day is 0.
+    fn key(&self) -> (u32, u8, u8) {
+        match *self {
+            Date::Year(y) => (y, 0, 0),
+            Date::YearMonth(y, m) => (y, m, 0),
+            Date::YearMonthDay(y, m, d) => (y, m, d),
+        }
+    }
+}
+/// Dates are ordered chronologically, where a date without a month or day comes before any date
+/// within it.
+impl Ord for Date {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+impl PartialOrd for Date {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl std::fmt::Display for Date {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Date::Year(y) => write!(f, "{y:04}"),
+            Date::YearMonth(y, m) => write!(f, "{y:04}-{m:02}"),
+            Date::YearMonthDay(y, m, d) => write!(f, "{y:04}-{m:02}-{d:02}"),
+        }
+    }
+}
 impl From<u32> for Date {
     fn from(value: u32) -> Self {
         Date::Year(value)
@@ -270,7 +854,7 @@ impl From<Date> for u32 {
 impl FromStr for Date {
     type Err = QueryParseError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut x = s.strip_prefix('d').unwrap().split('-');
+        let mut x = s.strip_prefix(['d', 'D']).unwrap_or(s).split('-');
 
         let y = x.next().map(|y| {
             y.parse()
@@ -496,4 +1080,384 @@ mod test_parsing {
         assert_eq!(expr_exp, expr_act);
         assert_val!(expr_act.eval(&env()).unwrap(), true);
     }
+    #[test]
+    fn env_expr_5() {
+        let query = stringify!(!(Artist == "Greg") && Title == "Feather" || Title == "Aruarian Dance");
+        assert_val!(expr(query).eval(&env()).unwrap(), true);
+    }
+
+    #[test]
+    fn logic_expr_1() {
+        assert_val!(expr("Title == \"Feather\" && Artist == \"Nujabes\"").eval(&env()).unwrap(), true);
+        assert_val!(expr("Title == \"Feather\" && Artist == \"Greg\"").eval(&env()).unwrap(), false);
+        assert_val!(expr("Title == \"Greg\" && Artist == \"Nujabes\"").eval(&env()).unwrap(), false);
+        assert_val!(expr("Title == \"Feather\" || Artist == \"Greg\"").eval(&env()).unwrap(), true);
+        assert_val!(expr("Title == \"Greg\" || Artist == \"Nujabes\"").eval(&env()).unwrap(), true);
+        assert_val!(expr("Title == \"Greg\" || Artist == \"Greg\"").eval(&env()).unwrap(), false);
+    }
+    #[test]
+    fn logic_expr_2() {
+        assert_val!(expr("!(Title == \"Feather\")").eval(&env()).unwrap(), false);
+        assert_val!(expr("!(Title == \"Greg\")").eval(&env()).unwrap(), true);
+        assert!(matches!(expr("!1").eval(&env()), Err(QueryEvalError::InvalidNot(_))));
+    }
+    #[test]
+    fn logic_expr_3() {
+        let query = stringify!(Title == "Feather" || Title == "Greg" && Artist == "Greg");
+        assert!(matches!(expr(query), Expr::BinOp { op: BinaryOperator::Or, .. }));
+        assert_val!(expr(query).eval(&env()).unwrap(), true);
+
+        let query = stringify!(Artist == "Greg" && Title == "Greg" || Title == "Feather");
+        assert!(matches!(expr(query), Expr::BinOp { op: BinaryOperator::Or, .. }));
+        assert_val!(expr(query).eval(&env()).unwrap(), true);
+    }
+    #[test]
+    fn logic_expr_4() {
+        let err = expr("Title == 1").eval(&env()).unwrap_err();
+        assert!(matches!(err, QueryEvalError::MismatchingTypes(..)));
+        let message = err.to_string();
+        assert!(message.contains("Feather") && message.contains('1'), "{message}");
+    }
+
+    fn coerce_env() -> VorbisComment {
+        let mut v = VorbisComment::new();
+
+        v.set("TRACKNUMBER", vec!["03/12"]);
+        v.set("DISCNUMBER", vec![" 2 "]);
+        v.set("VOLUME", vec!["02"]);
+        v.set("ORIGINALDATE", vec!["1999-04-01"]);
+        v.set("DATE", vec!["2005"]);
+
+        v
+    }
+
+    fn eval_strict(expr: &Expr, env: &VorbisComment) -> Result<Value, QueryEvalError> {
+        expr.eval_in(&Env { comments: env, properties: None, group: None, options: EvalOptions { strict: true, ..Default::default() } })
+    }
+
+    #[test]
+    fn coerce_expr_1() {
+        let query = stringify!(Volume == 2);
+        let expr_exp = Expr::BinOp {
+            lhs: Box::new(Expr::Value(Value::Field("VOLUME".to_string()))),
+            op: BinaryOperator::Equals,
+            rhs: Box::new(Expr::Value(Value::Integer(2))),
+        };
+
+        let expr_act = expr(query);
+        assert_eq!(expr_exp, expr_act);
+        assert_val!(expr_act.eval(&coerce_env()).unwrap(), true);
+        assert!(matches!(eval_strict(&expr_act, &coerce_env()), Err(QueryEvalError::MismatchingTypes(..))));
+    }
+    #[test]
+    fn coerce_expr_2() {
+        let expr_act = expr(stringify!(Tracknumber == 3 && Date == d2005));
+        assert_val!(expr_act.eval(&coerce_env()).unwrap(), true);
+        assert!(matches!(eval_strict(&expr_act, &coerce_env()), Err(QueryEvalError::IntegerOperation(_))));
+    }
+    #[test]
+    fn coerce_expr_5() {
+        let query = stringify!(Discnumber == 2 && Albumartist != "Nujabes");
+        let expr_exp = Expr::BinOp {
+            lhs: Box::new(Expr::BinOp {
+                lhs: Box::new(Expr::Value(Value::Tag(FlacTags::Discnumber))),
+                op: BinaryOperator::Equals,
+                rhs: Box::new(Expr::Value(Value::Integer(2))),
+            }),
+            op: BinaryOperator::And,
+            rhs: Box::new(Expr::BinOp {
+                lhs: Box::new(Expr::Value(Value::Tag(FlacTags::Albumartist))),
+                op: BinaryOperator::NotEquals,
+                rhs: Box::new(Expr::Value(Value::String(vec!["Nujabes".to_string()]))),
+            }),
+        };
+
+        let expr_act = expr(query);
+        assert_eq!(expr_exp, expr_act);
+        assert_val!(expr("Discnumber == 2").eval(&coerce_env()).unwrap(), true);
+        assert!(matches!(eval_strict(&expr("Discnumber == 2"), &coerce_env()), Err(QueryEvalError::IntegerOperation(_))));
+    }
+    #[test]
+    fn coerce_expr_3() {
+        let expr_act = expr(stringify!(d1999-04 == Originaldate && Originaldate < d2000));
+        assert_val!(expr_act.eval(&coerce_env()).unwrap(), true);
+        assert_val!(expr(stringify!(Originaldate == d1999)).eval(&coerce_env()).unwrap(), true);
+    }
+    #[test]
+    fn coerce_expr_dates_strict() {
+        // dates are only truncated to the same precision when not strict
+        assert_val!(eval_strict(&expr(stringify!(Originaldate == d1999)), &coerce_env()).unwrap(), false);
+        assert_val!(eval_strict(&expr(stringify!(Originaldate > d1999)), &coerce_env()).unwrap(), true);
+        assert_val!(eval_strict(&expr(stringify!(Originaldate < d2000-01)), &coerce_env()).unwrap(), true);
+        assert_val!(eval_strict(&expr(stringify!(Date < d2005-06)), &coerce_env()).unwrap(), true);
+        assert_val!(expr(stringify!(Date < d2005-06)).eval(&coerce_env()).unwrap(), false);
+    }
+    #[test]
+    fn coerce_expr_4() {
+        // tags followed by other characters are non-standard fields
+        assert_eq!(expr("Titlesort"), Expr::Value(Value::Field("TITLESORT".to_string())));
+        assert!(matches!(expr("Title == 1").eval(&env()), Err(QueryEvalError::MismatchingTypes(..))));
+    }
+
+    #[test]
+    fn schema_expr_1() {
+        let schema = Schema::from_str("BPM = number\nCOMPILATION = bool").unwrap();
+        let mut v = VorbisComment::new();
+        v.set("BPM", vec!["120.50"]);
+        v.set("COMPILATION", vec!["1"]);
+        let env = Env { comments: &v, properties: None, group: None, options: EvalOptions { strict: false, schema: &schema } };
+
+        assert_val!(expr("Compilation && Bpm > 120 && Bpm <= 120.5").eval_in(&env).unwrap(), true);
+        assert_val!(expr("Compilation == false").eval_in(&env).unwrap(), false);
+        // without the schema the tags are strings
+        assert!(expr("Compilation && Bpm > 120").eval(&v).is_err());
+    }
+
+    fn picture_tag() -> Tag {
+        use metaflac::block::{Block, Picture, PictureType};
+
+        let mut tag = Tag::new();
+        let mut front = Picture::new();
+        front.picture_type = PictureType::CoverFront;
+        front.mime_type = "image/png".to_string();
+        front.width = 1200;
+        front.height = 1000;
+        front.data = vec![0; 2048];
+        let mut back = Picture::new();
+        back.picture_type = PictureType::CoverBack;
+        back.mime_type = "image/jpeg".to_string();
+        back.width = 500;
+        back.height = 1100;
+        back.data = vec![0; 1024];
+        tag.push_block(Block::Picture(front));
+        tag.push_block(Block::Picture(back));
+
+        tag
+    }
+
+    #[test]
+    fn property_expr_1() {
+        let query = stringify!(picture_type ?= "front" && picture_width > 1000);
+        let tag = picture_tag();
+        let env = Env { comments: &VorbisComment::new(), properties: Some(&Properties::read(Some(&tag))), group: None, options: EvalOptions::default() };
+
+        let expr_act = expr(query);
+        assert_eq!(expr("picture_type"), Expr::Value(Value::Property(Property::PictureType)));
+        assert_val!(expr_act.eval_in(&env).unwrap(), true);
+        assert_val!(expr("has_picture && picture_count == 2").eval_in(&env).unwrap(), true);
+        assert_val!(expr(r#"picture_mime == "image/png" && picture_height == 1000"#).eval_in(&env).unwrap(), true);
+        // only the front cover is described, not the back cover's MIME type with the front's size
+        assert_val!(expr(r#"picture_mime == "image/jpeg" || picture_height == 1100"#).eval_in(&env).unwrap(), false);
+        assert_val!(expr("picture_bytes > 5000000").eval_in(&env).unwrap(), false);
+    }
+    #[test]
+    fn property_expr_2() {
+        // files without pictures
+        assert_val!(expr("!has_picture && picture_count == 0").eval(&env()).unwrap(), true);
+        assert!(matches!(expr("picture_width > 0").eval(&env()), Err(QueryEvalError::NoFrontCover(_))));
+
+        // files without a front cover
+        let mut tag = picture_tag();
+        tag.remove_picture_type(metaflac::block::PictureType::CoverFront);
+        let env = Env { comments: &VorbisComment::new(), properties: Some(&Properties::read(Some(&tag))), group: None, options: EvalOptions::default() };
+        assert_val!(expr("has_picture").eval_in(&env).unwrap(), true);
+        assert!(matches!(expr(r#"picture_mime == "image/jpeg""#).eval_in(&env), Err(QueryEvalError::NoFrontCover(_))));
+    }
+    #[test]
+    fn property_expr_4() {
+        // files without a front cover don't match, rather than failing the whole query
+        let mut back = picture_tag();
+        back.remove_picture_type(metaflac::block::PictureType::CoverFront);
+        let files = [picture_tag(), Tag::new(), back].iter().map(Metadata::from).collect::<Vec<_>>();
+
+        let query = Query::from_str(r#"picture_mime == "image/png" && picture_width > 1000"#).unwrap();
+        assert_eq!(query.eval_all(&files, EvalOptions::default()).unwrap(), vec![true, false, false]);
+        let query = Query::from_str("!(picture_bytes > 5000000)").unwrap();
+        assert_eq!(query.eval_all(&files, EvalOptions::default()).unwrap(), vec![true, false, false]);
+    }
+
+    #[test]
+    fn property_expr_3() {
+        use metaflac::block::{Application, Block, StreamInfo};
+
+        let mut comments = VorbisComment::new();
+        comments.vendor_string = "reference libFLAC 1.1.2 20050205".to_string();
+        let mut application = Application::new();
+        application.id = b"ATCH".to_vec();
+        let mut tag = Tag::new();
+        tag.push_block(Block::StreamInfo(StreamInfo::new()));
+        tag.push_block(Block::Application(application));
+        tag.push_block(Block::Padding(0));
+        tag.push_block(Block::VorbisComment(comments));
+        let env = Env { comments: tag.vorbis_comments().unwrap(), properties: Some(&Properties::read(Some(&tag))), group: None, options: EvalOptions::default() };
+
+        assert_val!(expr(r#"vendor ?= "libFLAC 1.1" && md5_unset && padding_bytes == 0"#).eval_in(&env).unwrap(), true);
+        assert_val!(expr(r#"application_ids == "ATCH" && !has_cuesheet && !has_seektable"#).eval_in(&env).unwrap(), true);
+    }
+
+    fn files() -> Vec<VorbisComment> {
+        let mut a = VorbisComment::new();
+        a.set("ALBUM", vec!["Modal Soul"]);
+        a.set("DATE", vec!["2005"]);
+
+        let mut b = VorbisComment::new();
+        b.set("ALBUM", vec!["Modal Soul"]);
+        b.set("DATE", vec!["2006"]);
+
+        let mut c = VorbisComment::new();
+        c.set("ALBUM", vec!["Metaphorical Music"]);
+        c.set("DATE", vec!["2003"]);
+
+        vec![a, b, c]
+    }
+
+    fn eval_all(query: &str) -> Vec<bool> {
+        let files = files();
+        let files = files
+            .into_iter()
+            .map(|comments| Metadata { comments, properties: Properties::read(None) })
+            .collect::<Vec<_>>();
+        Query::from_str(query)
+            .unwrap()
+            .eval_all(&files, EvalOptions::default())
+            .unwrap()
+    }
+
+    #[test]
+    fn group_expr_1() {
+        let query = "group by Album having count() < 2";
+        let query_exp = Query {
+            expr: Expr::BinOp {
+                lhs: Box::new(Expr::Aggregate(Aggregate::Count, None)),
+                op: BinaryOperator::Less,
+                rhs: Box::new(Expr::Value(Value::Integer(2))),
+            },
+            group_by: Some(FlacTags::Album),
+        };
+
+        assert_eq!(query_exp, Query::from_str(query).unwrap());
+        assert_eq!(eval_all(query), vec![false, false, true]);
+    }
+    #[test]
+    fn group_expr_2() {
+        let query = "distinct(Date) > 1 within Album";
+        let query_exp = Query {
+            expr: Expr::BinOp {
+                lhs: Box::new(Expr::Aggregate(Aggregate::Distinct, Some(FlacTags::Date))),
+                op: BinaryOperator::Greater,
+                rhs: Box::new(Expr::Value(Value::Integer(1))),
+            },
+            group_by: Some(FlacTags::Album),
+        };
+
+        assert_eq!(query_exp, Query::from_str(query).unwrap());
+        assert_eq!(eval_all(query), vec![true, true, false]);
+    }
+    #[test]
+    fn group_expr_3() {
+        assert_eq!(eval_all("count() == 3"), vec![true, true, true]);
+        assert!(Query::from_str("count() == 3").unwrap().is_grouped());
+        assert!(!Query::from_str("Album == \"Modal Soul\"").unwrap().is_grouped());
+        assert_eq!(eval_all("count(Date) == 3 && distinct(Album) == 2"), vec![true, true, true]);
+    }
+    #[test]
+    fn group_expr_4() {
+        assert!(matches!(Query::from_str("distinct() > 1"), Err(QueryParseError::AggregateError(_))));
+        assert!(matches!(Query::from_str("count() > 1 within"), Err(QueryParseError::SyntaxError)));
+    }
+    #[test]
+    fn group_expr_untagged() {
+        let mut tags = [Tag::new(), Tag::new(), Tag::new()];
+        tags[0].vorbis_comments_mut().set("ALBUM", vec!["Modal Soul"]);
+        let files = tags.iter().map(Metadata::from).collect::<Vec<_>>();
+        let query = Query::from_str("group by Album having count() == 1").unwrap();
+
+        assert_eq!(query.eval_all(&files, EvalOptions::default()).unwrap(), vec![true, true, true]);
+    }
+    #[test]
+    fn group_expr_5() {
+        let mut tags = [Tag::new(), Tag::new()];
+        tags[0].vorbis_comments_mut().set("GENRE", vec!["Jazz"]);
+        let files = tags.iter().map(Metadata::from).collect::<Vec<_>>();
+        let query = Query::from_str("Genre == \"Jazz\"").unwrap();
+
+        assert!(matches!(query.eval_all(&files, EvalOptions::default()), Err(QueryEvalError::TagNotSet(_))));
+        assert_eq!(query.eval_all_set(&files, EvalOptions::default()).unwrap(), vec![true, false]);
+    }
+
+    fn assign(statements: &str) -> VorbisComment {
+        let mut tag = Tag::new();
+        let v = tag.vorbis_comments_mut();
+        v.set("TITLE", vec!["  Feather "]);
+        v.set("ALBUM", vec!["Modal Soul"]);
+        v.set("GENRE", vec!["Hip Hop"]);
+        v.set("DATE", vec!["2005"]);
+        Assignments::from_str(statements)
+            .unwrap()
+            .apply(&mut tag, EvalOptions::default())
+            .unwrap();
+        tag.vorbis_comments().unwrap().clone()
+    }
+
+    #[test]
+    fn assignment_1() {
+        let assignments = Assignments::from_str("Title = trim(Title); album = upper(Album) if Genre == \"Hip Hop\";").unwrap();
+        let assignments_exp = Assignments(vec![
+            Assignment {
+                field: "TITLE".to_string(),
+                value: Expr::Call(Function::Trim, vec![Expr::Value(Value::Tag(FlacTags::Title))]),
+                condition: None,
+            },
+            Assignment {
+                field: "ALBUM".to_string(),
+                value: Expr::Call(Function::Upper, vec![Expr::Value(Value::Tag(FlacTags::Album))]),
+                condition: Some(Expr::BinOp {
+                    lhs: Box::new(Expr::Value(Value::Tag(FlacTags::Genre))),
+                    op: BinaryOperator::Equals,
+                    rhs: Box::new(Expr::Value(Value::String(vec!["Hip Hop".to_string()]))),
+                }),
+            },
+        ]);
+        assert_eq!(assignments, assignments_exp);
+    }
+    #[test]
+    fn assignment_2() {
+        let v = assign("Title = trim(Title); Album = upper(Album) if Genre == \"Classical\"; Genre = lower(Genre)");
+        assert_eq!(v.get("TITLE").unwrap(), &vec!["Feather"]);
+        assert_eq!(v.get("ALBUM").unwrap(), &vec!["Modal Soul"]);
+        assert_eq!(v.get("GENRE").unwrap(), &vec!["hip hop"]);
+    }
+    #[test]
+    fn assignment_3() {
+        let v = assign("Year = Date; Comment = upper(Mood); Origin = \"Tokyo\" if Mood == \"calm\"; Title = Album");
+        assert_eq!(v.get("YEAR").unwrap(), &vec!["2005"]);
+        assert_eq!(v.get("COMMENT"), None);
+        assert_eq!(v.get("ORIGIN"), None);
+        assert_eq!(v.get("TITLE").unwrap(), &vec!["Modal Soul"]);
+    }
+    #[test]
+    fn assignment_5() {
+        let mut tag = Tag::new();
+        let v = tag.vorbis_comments_mut();
+        v.set("TRACKNUMBER", vec!["3/12"]);
+        v.set("DATE", vec!["2005-06-01T10:00"]);
+
+        let skipped = Assignments::from_str("Tracknumber = Tracknumber; Date = Date; Year = Date; Disc = Date if Date > d2000")
+            .unwrap()
+            .apply(&mut tag, EvalOptions::default())
+            .unwrap();
+        let v = tag.vorbis_comments().unwrap();
+        assert_eq!(v.get("TRACKNUMBER").unwrap(), &vec!["3/12"]);
+        assert_eq!(v.get("DATE").unwrap(), &vec!["2005-06-01T10:00"]);
+        assert_eq!(v.get("YEAR").unwrap(), &vec!["2005-06-01T10:00"]);
+        assert_eq!(v.get("DISC"), None);
+        assert!(matches!(skipped[..], [QueryEvalError::DateOperation(_)]));
+    }
+    #[test]
+    fn assignment_4() {
+        assert!(matches!(Assignments::from_str("Title = trim(Title, Album)"), Err(QueryParseError::FunctionError(..))));
+        assert!(matches!(Assignments::from_str("Title == Album"), Err(QueryParseError::SyntaxError)));
+        assert!(matches!(Assignments::from_str("Title = Album if"), Err(QueryParseError::SyntaxError)));
+        assert!(Assignments::from_str("Title = iffy").is_ok());
+    }
 }
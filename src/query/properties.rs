@@ -0,0 +1,186 @@
+use super::{Env, QueryEvalError, Value};
+use metaflac::block::{self, Block, BlockType, Picture};
+use metaflac::Tag;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+/// Properties of a file that are not stored as tags, such as embedded pictures and the structure
+/// of its metadata blocks.
+#[derive(Hash, Debug, Clone, Copy, Ord, PartialEq, PartialOrd, Eq)]
+pub enum Property {
+    HasPicture,
+    PictureCount,
+    PictureType,
+    PictureWidth,
+    PictureHeight,
+    PictureMime,
+    PictureBytes,
+    HasCuesheet,
+    HasSeektable,
+    PaddingBytes,
+    ApplicationIds,
+    Vendor,
+    Md5Unset,
+}
+impl Property {
+    pub fn as_str(&self) -> &str {
+        use Property::*;
+        match self {
+            HasPicture => "has_picture",
+            PictureCount => "picture_count",
+            PictureType => "picture_type",
+            PictureWidth => "picture_width",
+            PictureHeight => "picture_height",
+            PictureMime => "picture_mime",
+            PictureBytes => "picture_bytes",
+            HasCuesheet => "has_cuesheet",
+            HasSeektable => "has_seektable",
+            PaddingBytes => "padding_bytes",
+            ApplicationIds => "application_ids",
+            Vendor => "vendor",
+            Md5Unset => "md5_unset",
+        }
+    }
+
+    const ALL: [Property; 13] = {
+        use Property::*;
+        [
+            HasPicture,
+            PictureCount,
+            PictureType,
+            PictureWidth,
+            PictureHeight,
+            PictureMime,
+            PictureBytes,
+            HasCuesheet,
+            HasSeektable,
+            PaddingBytes,
+            ApplicationIds,
+            Vendor,
+            Md5Unset,
+        ]
+    };
+
+    /// Evaluates the property for the file of the environment, as read into its [`Properties`].
+    pub fn eval(&self, env: &Env) -> Result<Value, QueryEvalError> {
+        match env.properties {
+            Some(properties) => properties.get(*self),
+            None => Properties::read(None).get(*self),
+        }
+    }
+
+    /// Reads the property from the file's metadata blocks, or `None` if the file doesn't have
+    /// it. Files without a tag behave as if they have no metadata blocks (other than their
+    /// comments).
+    ///
+    /// The MIME type, dimensions and size of a picture are of the front cover, so a query over them
+    /// describes a single picture, and they are unset if the file has no front cover.
+    fn read(&self, tag: Option<&Tag>) -> Option<Value> {
+        use Property::*;
+        let blocks = |t: BlockType| tag.into_iter().flat_map(move |tag| tag.get_blocks(t));
+        let pictures = tag
+            .into_iter()
+            .flat_map(|t| t.pictures())
+            .collect::<Vec<_>>();
+        let front = || {
+            pictures
+                .iter()
+                .find(|p| p.picture_type == block::PictureType::CoverFront)
+        };
+        let cover = |f: fn(&Picture) -> u32| front().map(|p| Value::Integer(f(p)));
+
+        Some(match self {
+            HasPicture => Value::Boolean(!pictures.is_empty()),
+            PictureCount => Value::Integer(pictures.len() as u32),
+            PictureType => Value::String(
+                pictures
+                    .iter()
+                    .map(|p| format!("{:?}", p.picture_type))
+                    .collect(),
+            ),
+            PictureMime => Value::String(vec![front()?.mime_type.clone()]),
+            PictureWidth => cover(|p| p.width)?,
+            PictureHeight => cover(|p| p.height)?,
+            PictureBytes => cover(|p| p.data.len() as u32)?,
+            HasCuesheet => Value::Boolean(blocks(BlockType::CueSheet).next().is_some()),
+            HasSeektable => Value::Boolean(blocks(BlockType::SeekTable).next().is_some()),
+            PaddingBytes => Value::Integer(
+                blocks(BlockType::Padding)
+                    .map(|b| match b {
+                        Block::Padding(n) => *n,
+                        _ => 0,
+                    })
+                    .sum(),
+            ),
+            ApplicationIds => Value::String(
+                blocks(BlockType::Application)
+                    .filter_map(|b| match b {
+                        Block::Application(a) => Some(application_id(&a.id)),
+                        _ => None,
+                    })
+                    .collect(),
+            ),
+            Vendor => Value::String(vec![tag?.vorbis_comments()?.vendor_string.clone()]),
+            Md5Unset => Value::Boolean(tag?.get_streaminfo()?.md5.iter().all(|b| *b == 0)),
+        })
+    }
+}
+
+/// The properties of a file, read up front so that its metadata blocks (such as picture data)
+/// needn't be kept to evaluate queries.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Properties(BTreeMap<Property, Value>);
+impl Properties {
+    pub fn read(tag: Option<&Tag>) -> Self {
+        Properties(
+            Property::ALL
+                .into_iter()
+                .filter_map(|p| p.read(tag).map(|v| (p, v)))
+                .collect(),
+        )
+    }
+
+    /// The value of the property, erroring if the file doesn't have it.
+    pub fn get(&self, property: Property) -> Result<Value, QueryEvalError> {
+        use Property::*;
+        let name = property.as_str().to_string();
+        self.0.get(&property).cloned().ok_or(match property {
+            PictureMime | PictureWidth | PictureHeight | PictureBytes => {
+                QueryEvalError::NoFrontCover(name)
+            }
+            _ => QueryEvalError::TagNotSet(name),
+        })
+    }
+}
+
+impl FromStr for Property {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use Property::*;
+        Ok(match s.to_lowercase().as_str() {
+            "has_picture" => HasPicture,
+            "picture_count" => PictureCount,
+            "picture_type" => PictureType,
+            "picture_width" => PictureWidth,
+            "picture_height" => PictureHeight,
+            "picture_mime" => PictureMime,
+            "picture_bytes" => PictureBytes,
+            "has_cuesheet" => HasCuesheet,
+            "has_seektable" => HasSeektable,
+            "padding_bytes" => PaddingBytes,
+            "application_ids" => ApplicationIds,
+            "vendor" => Vendor,
+            "md5_unset" => Md5Unset,
+            _ => Err(s.to_string())?,
+        })
+    }
+}
+
+/// Application IDs are registered as 4 printable ASCII characters, otherwise they are shown in
+/// hexadecimal.
+fn application_id(id: &[u8]) -> String {
+    match id.iter().all(|c| c.is_ascii_graphic() || *c == b' ') {
+        true => String::from_utf8_lossy(id).to_string(),
+        false => id.iter().map(|c| format!("{c:02x}")).collect(),
+    }
+}
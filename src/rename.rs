@@ -0,0 +1,232 @@
+use crate::template::Template;
+use colored::Colorize;
+use itertools::Itertools;
+use metaflac::block::VorbisComment;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Extensions of files in an album's directory that are moved along with the album.
+const SIDECAR_EXTENSIONS: &[&str] = &[
+    "jpg", "jpeg", "png", "gif", "webp", "bmp", "cue", "log", "m3u", "m3u8", "txt", "nfo",
+];
+
+#[derive(Error, Debug)]
+pub enum RenameError {
+    #[error("Could not move file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not rename {0}, as the template gives an empty directory or file name")]
+    Empty(PathBuf),
+    #[error("Could not rename both {0} and {1} to {2}")]
+    Collision(PathBuf, PathBuf, PathBuf),
+    #[error("Could not rename {0}, as {1} already exists")]
+    Exists(PathBuf, PathBuf),
+}
+
+/// Plans moving each file to the path given by rendering the template with its tags, along with
+/// any sidecar files.
+///
+/// Files sharing a file's name (e.g. `01 Title.cue`) are moved with it, and album level files
+/// (e.g. `cover.jpg`) are moved when every FLAC file in their directory is moved to the same
+/// directory. The file's extension is added to the rendered path, unless it already ends with it.
+/// Nothing is planned if any file can't be moved, as either its path would be empty, it would
+/// overwrite an existing file, or several files would be moved to the same path.
+pub fn plan_renames(
+    files: &[(&Path, &VorbisComment)],
+    template: &Template,
+) -> Result<Vec<(PathBuf, PathBuf)>, RenameError> {
+    let mut moves = Vec::new();
+    for (path, meta) in files {
+        let canonical = path.canonicalize().unwrap_or(path.to_path_buf());
+        let mut target = template
+            .render_path(meta, &canonical)
+            .ok_or(RenameError::Empty(path.to_path_buf()))?;
+        if let Some(ext) = path.extension() {
+            if !target
+                .extension()
+                .is_some_and(|e| e.eq_ignore_ascii_case(ext))
+            {
+                target.as_mut_os_string().push(".");
+                target.as_mut_os_string().push(ext);
+            }
+        }
+
+        // sidecars sharing the file's name
+        siblings(path)
+            .filter(|p| p != path && p.file_stem() == path.file_stem())
+            .filter_map(|p| Some((p.clone(), target.with_extension(p.extension()?))))
+            .for_each(|m| moves.push(m));
+        moves.push((path.to_path_buf(), target));
+    }
+
+    // album level sidecars, moved when all of a directory's FLAC files go to the same directory
+    let moved = moves.clone();
+    let directories = moved
+        .iter()
+        .filter(|(from, _)| is_flac(from))
+        .into_group_map_by(|(from, _)| parent(from));
+    for (dir, files) in directories {
+        let targets = files
+            .iter()
+            .map(|(_, to)| parent(to))
+            .unique()
+            .collect_vec();
+        let flacs = siblings(files[0].0.as_path())
+            .filter(|p| is_flac(p))
+            .count();
+        if targets.len() != 1 || flacs != files.len() || targets[0] == dir {
+            continue;
+        }
+
+        siblings(files[0].0.as_path())
+            .filter(|p| !moved.iter().any(|(from, _)| from == p))
+            .filter(|p| {
+                p.extension()
+                    .and_then(|e| e.to_str())
+                    .is_some_and(|e| SIDECAR_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+            })
+            .filter_map(|p| Some((p.clone(), targets[0].join(p.file_name()?))))
+            .for_each(|m| moves.push(m));
+    }
+
+    // check for collisions before anything is moved
+    moves.retain(|(from, to)| from != to);
+    let mut targets = BTreeMap::new();
+    for (from, to) in &moves {
+        if let Some(other) = targets.insert(to.clone(), from.clone()) {
+            Err(RenameError::Collision(other, from.clone(), to.clone()))?
+        }
+        let same_file = match (from.canonicalize(), to.canonicalize()) {
+            (Ok(a), Ok(b)) => a == b,
+            _ => false,
+        };
+        if to.exists() && !same_file {
+            Err(RenameError::Exists(from.clone(), to.clone()))?
+        }
+    }
+
+    Ok(moves)
+}
+
+/// Moves each file, creating any missing directories and removing directories left empty.
+pub fn rename_files(moves: &[(PathBuf, PathBuf)]) -> Result<(), RenameError> {
+    for (from, to) in moves {
+        if let Some(dir) = to.parent().filter(|d| !d.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        if fs::rename(from, to).is_err() {
+            /* fallback for moves between filesystems */
+            fs::copy(from, to)?;
+            fs::remove_file(from)?;
+        }
+    }
+    moves
+        .iter()
+        .map(|(from, _)| parent(from))
+        .unique()
+        .for_each(|dir| _ = fs::remove_dir(dir));
+
+    Ok(())
+}
+
+/// Prints each move, for previewing a dry run.
+pub fn print_renames(moves: &[(PathBuf, PathBuf)]) {
+    moves.iter().for_each(|(from, to)| {
+        println!(
+            "{} {} {}",
+            from.to_string_lossy().red(),
+            "->".bold(),
+            to.to_string_lossy().green()
+        )
+    });
+}
+
+fn parent(path: &Path) -> PathBuf {
+    match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+/// Files in the same directory as the path (including itself).
+fn siblings(path: &Path) -> impl Iterator<Item = PathBuf> + '_ {
+    let prefix = path.parent().filter(|p| !p.as_os_str().is_empty());
+    fs::read_dir(parent(path))
+        .into_iter()
+        .flatten()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().is_file())
+        .map(move |e| match prefix {
+            Some(dir) => dir.join(e.file_name()),
+            None => PathBuf::from(e.file_name()),
+        })
+}
+
+fn is_flac(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("flac"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::str::FromStr;
+
+    fn meta(title: &str, tracknumber: &str) -> VorbisComment {
+        let mut meta = VorbisComment::new();
+        meta.set("ALBUMARTIST", vec!["Nujabes"]);
+        meta.set("ALBUM", vec!["Modal Soul"]);
+        meta.set("TITLE", vec![title]);
+        meta.set("TRACKNUMBER", vec![tracknumber]);
+        meta
+    }
+
+    #[test]
+    fn test_plan_renames() {
+        let dir = std::env::temp_dir().join(format!("flaq-rename-{}", std::process::id()));
+        let src = dir.join("rip");
+        fs::create_dir_all(&src).unwrap();
+        ["1.flac", "2.flac", "1.cue", "cover.jpg", "notes.doc"]
+            .iter()
+            .for_each(|f| fs::write(src.join(f), "").unwrap());
+        let (one, two) = (src.join("1.flac"), src.join("2.flac"));
+        let (feather, ordinary) = (meta("Feather Pt.1", "3"), meta("Ordinary/Joe", "12"));
+        let files = [(one.as_path(), &feather), (two.as_path(), &ordinary)];
+        let template = format!(
+            "{}/{{albumartist}}/{{album}}/{{tracknumber:02}} {{title}}",
+            dir.to_str().unwrap()
+        );
+
+        let moves = plan_renames(&files, &Template::from_str(&template).unwrap()).unwrap();
+        let album = dir.join("Nujabes/Modal Soul");
+        let expected = [
+            (src.join("1.cue"), album.join("03 Feather Pt.1.cue")),
+            (src.join("1.flac"), album.join("03 Feather Pt.1.flac")),
+            (src.join("2.flac"), album.join("12 Ordinary_Joe.flac")),
+            (src.join("cover.jpg"), album.join("cover.jpg")),
+        ];
+        assert_eq!(moves.into_iter().sorted().collect_vec(), expected);
+
+        let collision = Template::from_str(&format!("{}/{{album}}", dir.to_str().unwrap()));
+        assert!(matches!(
+            plan_renames(&files, &collision.unwrap()),
+            Err(RenameError::Collision(..))
+        ));
+        let exists = Template::from_str(&format!("{}/rip/2", dir.to_str().unwrap()));
+        assert!(matches!(
+            plan_renames(&files[..1], &exists.unwrap()),
+            Err(RenameError::Exists(..))
+        ));
+        let empty = Template::from_str("{date}/{title}").unwrap();
+        assert!(matches!(
+            plan_renames(&files, &empty),
+            Err(RenameError::Empty(_))
+        ));
+
+        rename_files(&expected).unwrap();
+        assert!(album.join("12 Ordinary_Joe.flac").exists());
+        assert!(src.join("notes.doc").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,163 @@
+use regex::{Regex, RegexBuilder};
+use std::str::FromStr;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum SubstitutionError {
+    #[error("Substitution must be given as `s/pattern/replacement/flags`, found `{0}`")]
+    Syntax(String),
+    #[error("Unknown substitution flag `{0}`, expected `g` or `i`")]
+    Flag(char),
+    #[error("Invalid substitution pattern: {0}")]
+    Regex(#[from] regex::Error),
+}
+
+/// A sed style substitution, given as `s/pattern/replacement/flags`.
+///
+/// Any delimiter can be used in place of `/` (e.g. `s|a/b|c|`), and can be escaped with `\`. The
+/// replacement can refer to capture groups with `\1` through `\9`, and to the whole match with `&`.
+/// The flags are `g` to replace every match rather than the first, and `i` to ignore case.
+#[derive(Debug, Clone)]
+pub struct Substitution {
+    regex: Regex,
+    /// Replacement, converted to the syntax of [`Regex::replace`].
+    replacement: String,
+    global: bool,
+}
+impl Substitution {
+    pub fn apply(&self, s: &str) -> String {
+        match self.global {
+            true => self.regex.replace_all(s, self.replacement.as_str()),
+            false => self.regex.replace(s, self.replacement.as_str()),
+        }
+        .into_owned()
+    }
+}
+impl PartialEq for Substitution {
+    fn eq(&self, other: &Self) -> bool {
+        self.regex.as_str() == other.regex.as_str()
+            && self.replacement == other.replacement
+            && self.global == other.global
+    }
+}
+impl Eq for Substitution {}
+impl FromStr for Substitution {
+    type Err = SubstitutionError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let syntax = || SubstitutionError::Syntax(s.to_string());
+        let mut chars = s.chars();
+        if chars.next() != Some('s') {
+            Err(syntax())?
+        }
+        let delimiter = chars
+            .next()
+            .filter(|c| !c.is_alphanumeric() && *c != '\\')
+            .ok_or_else(syntax)?;
+
+        // split on unescaped delimiters, only unescaping the delimiter itself
+        let mut parts = vec![String::new()];
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some(c) if c == delimiter => parts.last_mut().unwrap().push(c),
+                    Some(c) => parts.last_mut().unwrap().extend(['\\', c]),
+                    None => Err(syntax())?,
+                },
+                c if c == delimiter => parts.push(String::new()),
+                c => parts.last_mut().unwrap().push(c),
+            }
+        }
+        let [pattern, replacement, flags] = <[String; 3]>::try_from(parts).map_err(|_| syntax())?;
+
+        let mut global = false;
+        let mut builder = RegexBuilder::new(&pattern);
+        for flag in flags.chars() {
+            match flag {
+                'g' => global = true,
+                'i' => _ = builder.case_insensitive(true),
+                c => Err(SubstitutionError::Flag(c))?,
+            }
+        }
+
+        Ok(Substitution {
+            regex: builder.build()?,
+            replacement: convert_replacement(&replacement),
+            global,
+        })
+    }
+}
+
+/// Converts a sed replacement (`\1`, `&`) into the syntax used by the regex crate (`${1}`, `${0}`).
+fn convert_replacement(s: &str) -> String {
+    let mut out = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(d) if d.is_ascii_digit() => out.push_str(&format!("${{{d}}}")),
+                Some('n') => out.push('\n'),
+                Some('t') => out.push('\t'),
+                Some('$') => out.push_str("$$"),
+                Some(c) => out.push(c),
+                None => out.push('\\'),
+            },
+            '&' => out.push_str("${0}"),
+            '$' => out.push_str("$$"),
+            c => out.push(c),
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sub(s: &str, input: &str) -> String {
+        Substitution::from_str(s).unwrap().apply(input)
+    }
+
+    #[test]
+    fn test_substitution() {
+        assert_eq!(
+            sub(r"s/ \(Remastered\)$//", "Feather (Remastered)"),
+            "Feather"
+        );
+        assert_eq!(
+            sub(r"s/feat\./feat./i", "Luv (Sic) Feat. Shing02"),
+            "Luv (Sic) feat. Shing02"
+        );
+        assert_eq!(sub(r"s/o/0/", "foo"), "f0o");
+        assert_eq!(sub(r"s/o/0/g", "foo"), "f00");
+        assert_eq!(
+            sub(r"s/(\w+) - (\w+)/\2 - \1/", "Nujabes - Feather"),
+            "Feather - Nujabes"
+        );
+        assert_eq!(sub(r"s/\d+/[&]/g", "Part 1 and 2"), "Part [1] and [2]");
+        assert_eq!(sub(r"s|/|; |g", "AC/DC/Queen"), "AC; DC; Queen");
+        assert_eq!(sub(r"s/\//+/", "a/b"), "a+b");
+        assert_eq!(sub(r"s/\$/USD /", "$5"), "USD 5");
+        assert_eq!(sub(r"s/5/$1/", "5"), "$1");
+    }
+
+    #[test]
+    fn test_substitution_errors() {
+        assert!(matches!(
+            Substitution::from_str("s/a/b"),
+            Err(SubstitutionError::Syntax(_))
+        ));
+        assert!(matches!(
+            Substitution::from_str("y/a/b/"),
+            Err(SubstitutionError::Syntax(_))
+        ));
+        assert!(matches!(
+            Substitution::from_str("s/a/b/x"),
+            Err(SubstitutionError::Flag('x'))
+        ));
+        assert!(matches!(
+            Substitution::from_str("s/(/b/"),
+            Err(SubstitutionError::Regex(_))
+        ));
+    }
+}
@@ -0,0 +1,223 @@
+use crate::operations::Edit;
+use crate::query::{Assignments, Query, QueryParseError};
+use crate::tags::validate_field_name;
+use colored::Colorize;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::str::FromStr;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum RulesError {
+    #[error("Could not read rules: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid rules file: {0}")]
+    Syntax(#[from] toml::de::Error),
+    #[error("Invalid query in rule `{0}`: {1}")]
+    Query(String, QueryParseError),
+    #[error("Invalid edit in rule `{0}`: {1}")]
+    Edit(String, String),
+}
+
+/// A query, and the edits applied to the files it matches.
+#[derive(Debug, Clone)]
+pub struct Rule {
+    pub name: String,
+    pub when: Query,
+    pub edits: Vec<Edit>,
+    pub assignments: Option<Assignments>,
+}
+impl Rule {
+    /// The tags edited by the rule.
+    pub fn fields(&self) -> impl Iterator<Item = &str> {
+        self.edits
+            .iter()
+            .map(Edit::field)
+            .chain(self.assignments.iter().flat_map(Assignments::fields))
+    }
+}
+
+/// Rules applied in order to every file of a library, read from a TOML file of `[[rule]]` tables.
+///
+/// Each rule has a `when` query, along with any of `set` and `append` (tables of tags to values),
+/// `delete` (a list of tags), and `eval` (assignment statements). An optional `name` is used when
+/// reporting, defaulting to the rule's position, and `template = true` renders set and appended
+/// values as templates. Within a rule tags are deleted, set, appended, and then evaluated, and each
+/// rule's query sees the edits of the rules before it.
+#[derive(Debug, Clone, Default)]
+pub struct Rules(pub Vec<Rule>);
+impl Rules {
+    pub fn from_path(path: &Path) -> Result<Self, RulesError> {
+        std::fs::read_to_string(path)?.parse()
+    }
+
+    /// The tags edited by any rule.
+    pub fn fields(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().flat_map(Rule::fields)
+    }
+}
+impl FromStr for Rules {
+    type Err = RulesError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let file: RulesFile = toml::from_str(s)?;
+
+        file.rule
+            .into_iter()
+            .enumerate()
+            .map(|(i, rule)| {
+                let name = rule.name.unwrap_or_else(|| format!("rule {}", i + 1));
+                let when =
+                    Query::from_str(&rule.when).map_err(|e| RulesError::Query(name.clone(), e))?;
+                let assignments = match &rule.eval {
+                    Some(eval) => Some(
+                        Assignments::from_str(eval)
+                            .map_err(|e| RulesError::Query(name.clone(), e))?,
+                    ),
+                    None => None,
+                };
+
+                let edits = rule
+                    .delete
+                    .into_iter()
+                    .map(|f| Edit::Delete(f.to_ascii_uppercase()))
+                    .chain(
+                        rule.set
+                            .into_iter()
+                            .map(|(f, v)| Edit::Set(f.to_ascii_uppercase(), v.into())),
+                    )
+                    .chain(
+                        rule.append
+                            .into_iter()
+                            .map(|(f, v)| Edit::Append(f.to_ascii_uppercase(), v.into())),
+                    )
+                    .map(|edit| {
+                        validate_field_name(edit.field())?;
+                        match rule.template {
+                            true => edit.into_template().map_err(|e| e.to_string()),
+                            false => Ok(edit),
+                        }
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| RulesError::Edit(name.clone(), e))?;
+
+                Ok(Rule {
+                    name,
+                    when,
+                    edits,
+                    assignments,
+                })
+            })
+            .collect::<Result<_, _>>()
+            .map(Rules)
+    }
+}
+
+/// Prints the number of files each rule matched, for reporting a dry run.
+pub fn print_matches(rules: &Rules, matched: &[usize]) {
+    rules.0.iter().zip(matched).for_each(|(rule, n)| {
+        println!(
+            "{:6} {} matched {} file(s)",
+            "Rule:".bold(),
+            rule.name.green(),
+            n
+        )
+    });
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RulesFile {
+    #[serde(default)]
+    rule: Vec<RuleEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleEntry {
+    name: Option<String>,
+    when: String,
+    #[serde(default)]
+    set: BTreeMap<String, Values>,
+    #[serde(default)]
+    append: BTreeMap<String, Values>,
+    #[serde(default)]
+    delete: Vec<String>,
+    eval: Option<String>,
+    #[serde(default)]
+    template: bool,
+}
+
+/// A single value, or a list of values.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Values {
+    One(String),
+    Many(Vec<String>),
+}
+impl From<Values> for Vec<String> {
+    fn from(values: Values) -> Self {
+        match values {
+            Values::One(v) => vec![v],
+            Values::Many(v) => v,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_rules_from_str() {
+        let rules = Rules::from_str(
+            r#"
+            [[rule]]
+            name = "hip hop"
+            when = 'Genre ?= "hip hop"'
+            set = { genre = "Hip-Hop" }
+            delete = ["comment"]
+
+            [[rule]]
+            when = "Date < d2000"
+            append = { grouping = ["Classics", "Pre 2000"] }
+            eval = "Title = trim(Title)"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(rules.0.len(), 2);
+        assert_eq!(rules.0[0].name, "hip hop");
+        assert_eq!(
+            rules.0[0].edits,
+            [
+                Edit::Delete("COMMENT".to_string()),
+                Edit::Set("GENRE".to_string(), vec!["Hip-Hop".to_string()]),
+            ]
+        );
+        assert_eq!(rules.0[1].name, "rule 2");
+        assert_eq!(
+            rules.fields().collect::<Vec<_>>(),
+            ["COMMENT", "GENRE", "GROUPING", "TITLE"]
+        );
+    }
+
+    #[test]
+    fn test_rules_errors() {
+        assert!(matches!(
+            Rules::from_str("[[rule]]\nset = { genre = \"Hip-Hop\" }"),
+            Err(RulesError::Syntax(_))
+        ));
+        assert!(matches!(
+            Rules::from_str("[[rule]]\nwhen = \"Genre ==\""),
+            Err(RulesError::Query(..))
+        ));
+        assert!(matches!(
+            Rules::from_str(
+                "[[rule]]\nwhen = \"true\"\nset = { genre = \"{genre\" }\ntemplate = true"
+            ),
+            Err(RulesError::Edit(..))
+        ));
+        assert!(Rules::from_str("[[rule]]\nwhen = \"true\"\nset = { genre = \"{genre\" }").is_ok());
+    }
+}
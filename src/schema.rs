@@ -0,0 +1,265 @@
+use crate::query::{coerce_boolean, coerce_date, coerce_integer, coerce_number, Date, Value};
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::path::Path;
+use std::str::FromStr;
+use thiserror::Error;
+
+/// Schema used when none is provided, only containing the built-in types.
+pub static EMPTY_SCHEMA: Schema = Schema {
+    fields: BTreeMap::new(),
+};
+
+#[derive(Error, Debug)]
+pub enum SchemaError {
+    #[error("Could not read schema: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid schema declaration on line {0}: `{1}`")]
+    Syntax(usize, String),
+    #[error("Integer range on line {0} is empty, as its start is after its end: `{1}`")]
+    EmptyRange(usize, String),
+    #[error("Unknown type `{1}` declared for tag {0}")]
+    UnknownType(String, String),
+    #[error("Tag {0} is declared as {1}, but was given `{2}`")]
+    InvalidValue(String, FieldType, String),
+}
+
+/// The type a tag's values are interpreted as.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FieldType {
+    String,
+    /// Unsigned integer, optionally restricted to an inclusive range.
+    Integer(Option<(u32, u32)>),
+    Number,
+    Date,
+    Boolean,
+}
+impl FieldType {
+    /// Reads a single tag value as this type, returning `None` if it is not valid.
+    ///
+    /// Unless `strict` is set, values are read leniently (see the `coerce_*` functions).
+    pub fn read(&self, s: &str, strict: bool) -> Option<Value> {
+        Some(match (self, strict) {
+            (Self::String, _) => Value::String(vec![s.to_string()]),
+            (Self::Integer(_), true) => Value::Integer(s.parse().ok()?),
+            (Self::Integer(_), false) => Value::Integer(coerce_integer(s)?),
+            (Self::Number, true) => Value::Number(s.parse().ok()?),
+            (Self::Number, false) => Value::Number(coerce_number(s)?),
+            (Self::Date, true) => Value::Date(Date::from_str(s).ok()?),
+            (Self::Date, false) => Value::Date(coerce_date(s)?),
+            (Self::Boolean, true) => Value::Boolean(match s {
+                "true" | "1" => true,
+                "false" | "0" => false,
+                _ => None?,
+            }),
+            (Self::Boolean, false) => Value::Boolean(coerce_boolean(s)?),
+        })
+    }
+
+    /// Checks a single tag value is valid for this type, including any declared range.
+    pub fn is_valid(&self, s: &str, strict: bool) -> bool {
+        match (self, self.read(s, strict)) {
+            (Self::Integer(Some((min, max))), Some(Value::Integer(i))) => {
+                (*min..=*max).contains(&i)
+            }
+            (_, v) => v.is_some(),
+        }
+    }
+}
+impl Display for FieldType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::String => write!(f, "string"),
+            Self::Integer(None) => write!(f, "integer"),
+            Self::Integer(Some((min, max))) => write!(f, "integer {min}..{max}"),
+            Self::Number => write!(f, "number"),
+            Self::Date => write!(f, "date"),
+            Self::Boolean => write!(f, "bool"),
+        }
+    }
+}
+impl FromStr for FieldType {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_whitespace();
+        let ty = words.next().unwrap_or_default().to_lowercase();
+        let range = words.next();
+        if words.next().is_some() {
+            Err(s.to_string())?
+        }
+
+        Ok(match (ty.as_str(), range) {
+            ("string" | "text", None) => Self::String,
+            ("integer" | "int", None) => Self::Integer(None),
+            ("integer" | "int", Some(range)) => {
+                let (min, max) = range.split_once("..").ok_or(s.to_string())?;
+                let min = min.parse().map_err(|_| s.to_string())?;
+                let max = max
+                    .trim_start_matches('=')
+                    .parse()
+                    .map_err(|_| s.to_string())?;
+                Self::Integer(Some((min, max)))
+            }
+            ("number" | "float", None) => Self::Number,
+            ("date", None) => Self::Date,
+            ("bool" | "boolean", None) => Self::Boolean,
+            _ => Err(s.to_string())?,
+        })
+    }
+}
+
+/// User declared types for tags, so they are queried and validated as something other than
+/// strings.
+///
+/// Declared in a file with one `TAG = type` per line, where type is one of `string`, `integer`
+/// (optionally followed by an inclusive range such as `0..100`), `number`, `date`, or `bool`.
+/// Lines starting with `#` are ignored.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Schema {
+    fields: BTreeMap<String, FieldType>,
+}
+impl Schema {
+    pub fn from_path(path: &Path) -> Result<Self, SchemaError> {
+        std::fs::read_to_string(path)?.parse()
+    }
+
+    /// Returns the declared type of the tag, falling back to the built-in types for dates
+    /// (`DATE`, `ORIGINALDATE`) and track/disc numbers. All other tags are strings.
+    pub fn field_type(&self, field: &str) -> FieldType {
+        let field = field.to_uppercase();
+        match self.fields.get(&field) {
+            Some(t) => *t,
+            None => match field.as_str() {
+                "DATE" | "ORIGINALDATE" => FieldType::Date,
+                "TRACKNUMBER" | "TRACKTOTAL" | "DISCNUMBER" | "DISCTOTAL" => {
+                    FieldType::Integer(None)
+                }
+                _ => FieldType::String,
+            },
+        }
+    }
+
+    /// Checks every value of a tag that has been declared in this schema.
+    pub fn validate(
+        &self,
+        field: &str,
+        values: &[String],
+        strict: bool,
+    ) -> Result<(), SchemaError> {
+        let field = field.to_uppercase();
+        if let Some(t) = self.fields.get(&field) {
+            values
+                .iter()
+                .try_for_each(|v| match t.is_valid(v, strict) {
+                    true => Ok(()),
+                    false => Err(SchemaError::InvalidValue(field.clone(), *t, v.clone())),
+                })?;
+        }
+
+        Ok(())
+    }
+}
+impl FromStr for Schema {
+    type Err = SchemaError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut fields = BTreeMap::new();
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (field, ty) = line
+                .split_once('=')
+                .ok_or(SchemaError::Syntax(i + 1, line.to_string()))?;
+            let field = field.trim().trim_matches('"').to_uppercase();
+            let ty = ty.trim().trim_matches('"');
+            if field.is_empty() {
+                Err(SchemaError::Syntax(i + 1, line.to_string()))?
+            }
+
+            let ty = FieldType::from_str(ty)
+                .map_err(|ty| SchemaError::UnknownType(field.clone(), ty))?;
+            if let FieldType::Integer(Some((min, max))) = ty {
+                if min > max {
+                    Err(SchemaError::EmptyRange(i + 1, line.to_string()))?
+                }
+            }
+            fields.insert(field, ty);
+        }
+
+        Ok(Schema { fields })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn schema() -> Schema {
+        Schema::from_str(
+            r#"
+            # custom tags
+            BPM = number
+            Rating = integer 0..100
+            ORIGINALDATE = "date"
+            COMPILATION = bool
+            "#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_parse_schema() {
+        let schema = schema();
+
+        assert_eq!(schema.field_type("bpm"), FieldType::Number);
+        assert_eq!(
+            schema.field_type("RATING"),
+            FieldType::Integer(Some((0, 100)))
+        );
+        assert_eq!(schema.field_type("ORIGINALDATE"), FieldType::Date);
+        assert_eq!(schema.field_type("COMPILATION"), FieldType::Boolean);
+        assert_eq!(schema.field_type("TRACKNUMBER"), FieldType::Integer(None));
+        assert_eq!(schema.field_type("TITLE"), FieldType::String);
+    }
+
+    #[test]
+    fn test_parse_schema_errors() {
+        assert!(matches!(
+            Schema::from_str("BPM"),
+            Err(SchemaError::Syntax(1, _))
+        ));
+        assert!(matches!(
+            Schema::from_str("BPM = colour"),
+            Err(SchemaError::UnknownType(..))
+        ));
+        assert!(matches!(
+            Schema::from_str("BPM = number\nRATING = integer 100..0"),
+            Err(SchemaError::EmptyRange(2, _))
+        ));
+        assert!(Schema::from_str("RATING = integer 5..5").is_ok());
+    }
+
+    #[test]
+    fn test_validate() {
+        let schema = schema();
+        let values = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        assert!(schema
+            .validate("RATING", &values(&["0", "100"]), false)
+            .is_ok());
+        assert!(schema.validate("RATING", &values(&["101"]), false).is_err());
+        assert!(schema.validate("BPM", &values(&["120.5"]), true).is_ok());
+        assert!(schema.validate("BPM", &values(&["fast"]), false).is_err());
+        assert!(schema
+            .validate("COMPILATION", &values(&["Yes"]), false)
+            .is_ok());
+        assert!(schema
+            .validate("COMPILATION", &values(&["Yes"]), true)
+            .is_err());
+        assert!(schema
+            .validate("TITLE", &values(&["anything"]), true)
+            .is_ok());
+    }
+}
@@ -1,4 +1,6 @@
 use crate::Fields;
+use itertools::Itertools;
+use regex::{Regex, RegexBuilder};
 use std::{collections::BTreeMap, str::FromStr};
 
 macro_rules! field {
@@ -26,6 +28,26 @@ pub enum FlacTags {
     Location,
     Contact,
     Isrc,
+    Albumartist,
+    Composer,
+    Conductor,
+    Discnumber,
+    Disctotal,
+    Tracktotal,
+    Originaldate,
+    Label,
+    Catalognumber,
+    Barcode,
+    MusicbrainzTrackid,
+    MusicbrainzReleasetrackid,
+    MusicbrainzAlbumid,
+    MusicbrainzArtistid,
+    MusicbrainzAlbumartistid,
+    MusicbrainzReleasegroupid,
+    MusicbrainzWorkid,
+    MusicbrainzDiscid,
+    Lyrics,
+    Comment,
 }
 impl FlacTags {
     pub fn from_args(f: Fields) -> BTreeMap<FlacTags, Vec<String>> {
@@ -47,6 +69,26 @@ impl FlacTags {
         field!(map, f.location, Location);
         field!(map, f.contact, Contact);
         field!(map, f.isrc, Isrc);
+        field!(map, f.albumartist, Albumartist);
+        field!(map, f.composer, Composer);
+        field!(map, f.conductor, Conductor);
+        field!(map, f.discnumber, Discnumber);
+        field!(map, f.disctotal, Disctotal);
+        field!(map, f.tracktotal, Tracktotal);
+        field!(map, f.originaldate, Originaldate);
+        field!(map, f.label, Label);
+        field!(map, f.catalognumber, Catalognumber);
+        field!(map, f.barcode, Barcode);
+        field!(map, f.musicbrainz_trackid, MusicbrainzTrackid);
+        field!(map, f.musicbrainz_releasetrackid, MusicbrainzReleasetrackid);
+        field!(map, f.musicbrainz_albumid, MusicbrainzAlbumid);
+        field!(map, f.musicbrainz_artistid, MusicbrainzArtistid);
+        field!(map, f.musicbrainz_albumartistid, MusicbrainzAlbumartistid);
+        field!(map, f.musicbrainz_releasegroupid, MusicbrainzReleasegroupid);
+        field!(map, f.musicbrainz_workid, MusicbrainzWorkid);
+        field!(map, f.musicbrainz_discid, MusicbrainzDiscid);
+        field!(map, f.lyrics, Lyrics);
+        field!(map, f.comment, Comment);
 
         map
     }
@@ -69,26 +111,42 @@ impl FlacTags {
             Location => "LOCATION",
             Contact => "CONTACT",
             Isrc => "ISRC",
+            Albumartist => "ALBUMARTIST",
+            Composer => "COMPOSER",
+            Conductor => "CONDUCTOR",
+            Discnumber => "DISCNUMBER",
+            Disctotal => "DISCTOTAL",
+            Tracktotal => "TRACKTOTAL",
+            Originaldate => "ORIGINALDATE",
+            Label => "LABEL",
+            Catalognumber => "CATALOGNUMBER",
+            Barcode => "BARCODE",
+            MusicbrainzTrackid => "MUSICBRAINZ_TRACKID",
+            MusicbrainzReleasetrackid => "MUSICBRAINZ_RELEASETRACKID",
+            MusicbrainzAlbumid => "MUSICBRAINZ_ALBUMID",
+            MusicbrainzArtistid => "MUSICBRAINZ_ARTISTID",
+            MusicbrainzAlbumartistid => "MUSICBRAINZ_ALBUMARTISTID",
+            MusicbrainzReleasegroupid => "MUSICBRAINZ_RELEASEGROUPID",
+            MusicbrainzWorkid => "MUSICBRAINZ_WORKID",
+            MusicbrainzDiscid => "MUSICBRAINZ_DISCID",
+            Lyrics => "LYRICS",
+            Comment => "COMMENT",
         }
     }
 }
+impl AsRef<str> for FlacTags {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
 impl FromStr for FlacTags {
     type Err = String;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         use FlacTags::*;
-        let s = s.to_uppercase();
+        let s = s.to_ascii_uppercase();
 
         // check for illegal characters
-        [':'].into_iter().try_for_each(|c| {
-            if s.find(c).is_some() {
-                Err(format!(
-                    "Attempted to use tag `{}` that contained illegal character `{}`",
-                    s, c
-                ))
-            } else {
-                Ok(())
-            }
-        })?;
+        validate_field_name(&s)?;
 
         // map to enum variant
         Ok(match s.as_str() {
@@ -107,7 +165,314 @@ impl FromStr for FlacTags {
             "LOCATION" => Location,
             "CONTACT" => Contact,
             "ISRC" => Isrc,
+            "ALBUMARTIST" => Albumartist,
+            "COMPOSER" => Composer,
+            "CONDUCTOR" => Conductor,
+            "DISCNUMBER" => Discnumber,
+            "DISCTOTAL" => Disctotal,
+            "TRACKTOTAL" => Tracktotal,
+            "ORIGINALDATE" => Originaldate,
+            "LABEL" => Label,
+            "CATALOGNUMBER" => Catalognumber,
+            "BARCODE" => Barcode,
+            "MUSICBRAINZ_TRACKID" => MusicbrainzTrackid,
+            "MUSICBRAINZ_RELEASETRACKID" => MusicbrainzReleasetrackid,
+            "MUSICBRAINZ_ALBUMID" => MusicbrainzAlbumid,
+            "MUSICBRAINZ_ARTISTID" => MusicbrainzArtistid,
+            "MUSICBRAINZ_ALBUMARTISTID" => MusicbrainzAlbumartistid,
+            "MUSICBRAINZ_RELEASEGROUPID" => MusicbrainzReleasegroupid,
+            "MUSICBRAINZ_WORKID" => MusicbrainzWorkid,
+            "MUSICBRAINZ_DISCID" => MusicbrainzDiscid,
+            "LYRICS" => Lyrics,
+            "COMMENT" => Comment,
             _ => Err(s)?,
         })
     }
 }
+
+/// Checks a field name only contains legal characters, being printable ASCII (0x20 through 0x7D)
+/// excluding `=`.
+pub fn validate_field_name(s: &str) -> Result<(), String> {
+    match s.chars().find(|c| !(' '..='}').contains(c) || *c == '=') {
+        Some(c) => Err(format!(
+            "Attempted to use tag `{}` that contained illegal character `{}`",
+            s, c
+        )),
+        None if s.is_empty() => Err("Attempted to use a tag with an empty name".to_string()),
+        None => Ok(()),
+    }
+}
+
+/// Legacy and non-standard tag names written by other taggers, and the tag each is migrated to.
+pub const LEGACY_TAGS: &[(&str, &str)] = &[
+    ("YEAR", "DATE"),
+    ("TRACK", "TRACKNUMBER"),
+    ("TRACKNUM", "TRACKNUMBER"),
+    ("TOTALTRACKS", "TRACKTOTAL"),
+    ("DISC", "DISCNUMBER"),
+    ("TOTALDISCS", "DISCTOTAL"),
+    ("ALBUM ARTIST", "ALBUMARTIST"),
+    ("ALBUM_ARTIST", "ALBUMARTIST"),
+    ("ORIGINALYEAR", "ORIGINALDATE"),
+    ("ORGANISATION", "ORGANIZATION"),
+    ("PUBLISHER", "LABEL"),
+    ("CATALOG", "CATALOGNUMBER"),
+    ("UPC", "BARCODE"),
+    ("COMMENTS", "COMMENT"),
+    ("UNSYNCEDLYRICS", "LYRICS"),
+    ("UNSYNCED LYRICS", "LYRICS"),
+];
+
+/// Parses tags given as `KEY=VALUE`, grouping the values of each (uppercased) key in the order they
+/// were given. A key given without a value has no values, for use when deleting.
+pub fn parse_tag_args(args: &[String]) -> Result<BTreeMap<String, Vec<String>>, String> {
+    let mut map: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for arg in args {
+        let (key, value) = match arg.split_once('=') {
+            Some((key, value)) => (key, Some(value)),
+            None => (arg.as_str(), None),
+        };
+        let key = key.to_ascii_uppercase();
+        validate_field_name(&key)?;
+
+        let values = map.entry(key).or_default();
+        if let Some(value) = value {
+            values.push(value.to_string());
+        }
+    }
+
+    Ok(map)
+}
+
+/// Parses tags given as `KEY`, without values, keeping the first of any repeated (uppercased) key.
+pub fn parse_field_args(args: &[String]) -> Result<Vec<String>, String> {
+    args.iter()
+        .map(|arg| match arg.contains('=') {
+            true => Err(format!("Expected `KEY` without a value, found `{arg}`")),
+            false => {
+                let key = arg.to_ascii_uppercase();
+                validate_field_name(&key).map(|_| key)
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map(|keys| keys.into_iter().unique().collect())
+}
+
+/// Parses values of tags, given as `KEY=VALUE`, in the order they were given.
+pub fn parse_value_args(args: &[String]) -> Result<Vec<(String, String)>, String> {
+    args.iter()
+        .map(|arg| {
+            let (key, value) = arg
+                .split_once('=')
+                .ok_or_else(|| format!("Expected `KEY=VALUE`, found `{arg}`"))?;
+            let key = key.to_ascii_uppercase();
+            validate_field_name(&key)?;
+
+            Ok((key, value.to_string()))
+        })
+        .collect()
+}
+
+/// Parses values to insert at a position, given as `KEY:POSITION=VALUE`.
+pub fn parse_insert_args(args: &[String]) -> Result<Vec<(String, usize, String)>, String> {
+    args.iter()
+        .map(|arg| {
+            let err = || format!("Expected `KEY:POSITION=VALUE`, found `{arg}`");
+            let (key, value) = arg.split_once('=').ok_or_else(err)?;
+            let (key, position) = key.rsplit_once(':').ok_or_else(err)?;
+            let key = key.to_ascii_uppercase();
+            validate_field_name(&key)?;
+
+            Ok((key, position.parse().map_err(|_| err())?, value.to_string()))
+        })
+        .collect()
+}
+
+/// Parses limits on the number of values of a tag, given as `KEY=MAX`.
+pub fn parse_max_args(args: &[String]) -> Result<Vec<(String, usize)>, String> {
+    parse_tag_args(args)?
+        .into_iter()
+        .map(|(key, values)| match values.last().map(|v| v.parse()) {
+            Some(Ok(max)) => Ok((key, max)),
+            _ => Err(format!("Expected `{key}=MAX`, where MAX is a number")),
+        })
+        .collect()
+}
+
+/// Parses the separator to join the values of a tag with, given as `KEY=SEPARATOR`.
+pub fn parse_join_args(args: &[String]) -> Result<Vec<(String, String)>, String> {
+    parse_tag_args(args)?
+        .into_iter()
+        .map(|(key, values)| match values.last() {
+            Some(separator) => Ok((key, separator.to_owned())),
+            None => Err(format!("Expected `{key}=SEPARATOR`")),
+        })
+        .collect()
+}
+
+/// Parses a source and destination tag, given as `SRC:DST`, split on the last `:`.
+pub fn parse_copy_args(args: &[String]) -> Result<Vec<(String, String)>, String> {
+    args.iter()
+        .map(|arg| {
+            let (src, dst) = arg
+                .rsplit_once(':')
+                .ok_or_else(|| format!("Expected `SRC:DST`, found `{arg}`"))?;
+            let (src, dst) = (src.to_ascii_uppercase(), dst.to_ascii_uppercase());
+            validate_field_name(&src)?;
+            validate_field_name(&dst)?;
+
+            Ok((src, dst))
+        })
+        .collect()
+}
+
+/// Parses pairs of old and new tag names, given as consecutive arguments.
+pub fn parse_rename_args(args: &[String]) -> Result<Vec<(String, String)>, String> {
+    args.chunks(2)
+        .map(|pair| {
+            let [old, new] = pair else {
+                Err(format!("Expected `OLD NEW`, found `{}`", pair.join(" ")))?
+            };
+            let (old, new) = (old.to_ascii_uppercase(), new.to_ascii_uppercase());
+            validate_field_name(&old)?;
+            validate_field_name(&new)?;
+
+            Ok((old, new))
+        })
+        .collect()
+}
+
+/// Parses patterns of tags to keep, where `*` matches any characters and `?` matches a single
+/// character. Patterns are case-insensitive.
+pub fn parse_keep_args(args: &[impl AsRef<str>]) -> Vec<Regex> {
+    args.iter()
+        .map(|arg| {
+            let pattern = regex::escape(arg.as_ref())
+                .replace(r"\*", ".*")
+                .replace(r"\?", ".");
+            RegexBuilder::new(&format!("^{pattern}$"))
+                .case_insensitive(true)
+                .build()
+                .expect("escaped pattern is a valid regex")
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_validate_field_name() {
+        assert!(validate_field_name("ALBUMARTIST").is_ok());
+        assert!(validate_field_name("ALBUM ARTIST").is_ok());
+        assert!(validate_field_name("REPLAYGAIN_TRACK_GAIN").is_ok());
+        assert!(validate_field_name("KEY:WITH:COLONS").is_ok());
+        assert!(validate_field_name("KEY=VALUE").is_err());
+        assert!(validate_field_name("~KEY").is_err());
+        assert!(validate_field_name("KÉY").is_err());
+        assert!(validate_field_name("").is_err());
+    }
+
+    #[test]
+    fn test_parse_tag_args() {
+        let args = [
+            "discnumber=1",
+            "COMPOSER=Bach",
+            "Composer=Handel=Haendel",
+            "COMMENT",
+        ]
+        .map(String::from);
+        let mut expected = BTreeMap::new();
+        expected.insert("DISCNUMBER".to_string(), vec!["1".to_string()]);
+        expected.insert(
+            "COMPOSER".to_string(),
+            vec!["Bach".to_string(), "Handel=Haendel".to_string()],
+        );
+        expected.insert("COMMENT".to_string(), vec![]);
+
+        assert_eq!(parse_tag_args(&args).unwrap(), expected);
+        assert!(parse_tag_args(&["BAD~KEY=1".to_string()]).is_err());
+        // non-ASCII keys are rejected, rather than uppercased into valid ones (`ß` to `SS`)
+        assert!(parse_tag_args(&["straße=1".to_string()]).is_err());
+        assert!(FlacTags::from_str("straße").is_err());
+    }
+
+    #[test]
+    fn test_parse_value_args() {
+        let args = ["artist=Nujabes", "ARTIST=Cise Starr", "comment=a=b"].map(String::from);
+        let expected = vec![
+            ("ARTIST".to_string(), "Nujabes".to_string()),
+            ("ARTIST".to_string(), "Cise Starr".to_string()),
+            ("COMMENT".to_string(), "a=b".to_string()),
+        ];
+
+        assert_eq!(parse_value_args(&args).unwrap(), expected);
+        assert!(parse_value_args(&["ARTIST".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_parse_insert_args() {
+        let args = ["artist:1=Nujabes", "SOME:KEY:2=a=b"].map(String::from);
+        let expected = vec![
+            ("ARTIST".to_string(), 1, "Nujabes".to_string()),
+            ("SOME:KEY".to_string(), 2, "a=b".to_string()),
+        ];
+
+        assert_eq!(parse_insert_args(&args).unwrap(), expected);
+        assert!(parse_insert_args(&["ARTIST=Nujabes".to_string()]).is_err());
+        assert!(parse_insert_args(&["ARTIST:first=Nujabes".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_parse_field_args() {
+        let args = ["artist", "GENRE", "Artist"].map(String::from);
+
+        assert_eq!(parse_field_args(&args).unwrap(), ["ARTIST", "GENRE"]);
+        assert!(parse_field_args(&["artist=foo".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_parse_join_args() {
+        let args = ["artist=; ", "GENRE= / "].map(String::from);
+        let expected = vec![
+            ("ARTIST".to_string(), "; ".to_string()),
+            ("GENRE".to_string(), " / ".to_string()),
+        ];
+
+        assert_eq!(parse_join_args(&args).unwrap(), expected);
+        assert!(parse_join_args(&["ARTIST".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_parse_copy_args() {
+        let args = [
+            "artist:albumartist",
+            "COMMENT:DESCRIPTION",
+            "SOURCE:URL:WEBSITE",
+        ]
+        .map(String::from);
+        let expected = vec![
+            ("ARTIST".to_string(), "ALBUMARTIST".to_string()),
+            ("COMMENT".to_string(), "DESCRIPTION".to_string()),
+            ("SOURCE:URL".to_string(), "WEBSITE".to_string()),
+        ];
+
+        assert_eq!(parse_copy_args(&args).unwrap(), expected);
+        assert!(parse_copy_args(&["ARTIST".to_string()]).is_err());
+        assert!(parse_copy_args(&["ARTIST:".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_parse_keep_args() {
+        let keep = parse_keep_args(&["replaygain_*", "ITUNES?", "A.B"]);
+        let kept = |k: &str| keep.iter().any(|r| r.is_match(k));
+
+        assert!(kept("REPLAYGAIN_TRACK_GAIN"));
+        assert!(kept("ITUNES1"));
+        assert!(!kept("ITUNES"));
+        assert!(kept("A.B"));
+        assert!(!kept("AXB"));
+        assert!(!kept("MY_REPLAYGAIN_TRACK_GAIN"));
+    }
+}
@@ -0,0 +1,423 @@
+use crate::query::coerce_integer;
+use crate::tags::{validate_field_name, FlacTags};
+use metaflac::block::VorbisComment;
+use regex::Regex;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum TemplateError {
+    #[error("Unclosed `{{` in template `{0}`, use `{{{{` for a literal brace")]
+    Unclosed(String),
+    #[error("Unmatched `}}` in template `{0}`, use `}}}}` for a literal brace")]
+    Unmatched(String),
+    #[error("Invalid variable `{{{0}}}` in template")]
+    Variable(String),
+    #[error(
+        "Unknown variable `{{{0}}}` in template, expected a standard tag, `filename`, or `dirname`"
+    )]
+    Unknown(String),
+    #[error("Unclosed `%` in path pattern `{0}`, use `%%` for a literal percent sign")]
+    UnclosedPattern(String),
+    #[error("{0}")]
+    Field(String),
+    #[error("Unknown template filter `{0}`, expected one of `upper`, `lower`, or `trim`")]
+    Filter(String),
+}
+
+/// Transformation applied to a variable's value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    Upper,
+    Lower,
+    Trim,
+}
+impl Filter {
+    fn apply(&self, s: &str) -> String {
+        match self {
+            Self::Upper => s.to_uppercase(),
+            Self::Lower => s.to_lowercase(),
+            Self::Trim => s.trim().to_string(),
+        }
+    }
+}
+impl FromStr for Filter {
+    type Err = TemplateError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.trim().to_lowercase().as_str() {
+            "upper" => Self::Upper,
+            "lower" => Self::Lower,
+            "trim" => Self::Trim,
+            _ => Err(TemplateError::Filter(s.to_string()))?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Variable {
+        /// Uppercased tag name, or one of the path variables.
+        name: String,
+        /// Minimum width, and whether to pad with zeros (rather than spaces).
+        width: Option<(usize, bool)>,
+        filters: Vec<Filter>,
+    },
+}
+
+/// A value that refers to other tags of the file, such as `{title} (Live)`.
+///
+/// Variables are given as `{name}`, where name is a standard tag (using its first value, or nothing
+/// if it is unset) or one of `filename` (the file's name without extension) and `dirname` (the
+/// name of the file's directory). A variable can be padded to a width with `{name:3}`, or with
+/// zeros using `{name:03}`, and transformed with any number of filters, e.g.
+/// `{artist|trim|upper}`. Literal braces are written as `{{` and `}}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    segments: Vec<Segment>,
+}
+impl Template {
+    /// Renders the template for a file, using the given tags. The path should be canonical, so the
+    /// directory of a file in the working directory is known.
+    pub fn render(&self, meta: &VorbisComment, path: &Path) -> String {
+        self.render_with(meta, path, |v| v)
+    }
+
+    /// Renders the template as a path for a file, where variables are made safe to use in file
+    /// names, and surrounding whitespace is removed from each directory and file name. Returns
+    /// `None` if any directory or file name would be empty (e.g. from an unset tag).
+    pub fn render_path(&self, meta: &VorbisComment, path: &Path) -> Option<PathBuf> {
+        let rendered = self.render_with(meta, path, sanitize);
+        let absolute =
+            matches!(self.segments.first(), Some(Segment::Literal(l)) if l.starts_with('/'));
+        let components = rendered.split('/').map(str::trim).collect::<Vec<_>>();
+        match components
+            .iter()
+            .skip(absolute as usize)
+            .all(|c| !c.is_empty())
+        {
+            true => Some(PathBuf::from(components.join("/"))),
+            false => None,
+        }
+    }
+
+    fn render_with(
+        &self,
+        meta: &VorbisComment,
+        path: &Path,
+        f: impl Fn(String) -> String,
+    ) -> String {
+        self.segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Literal(s) => s.to_owned(),
+                Segment::Variable {
+                    name,
+                    width,
+                    filters,
+                } => {
+                    let value = lookup(name, meta, path);
+                    let value = filters.iter().fold(value, |v, f| f.apply(&v));
+                    f(pad(value, *width))
+                }
+            })
+            .collect()
+    }
+}
+impl FromStr for Template {
+    type Err = TemplateError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        let mut chars = s.chars().peekable();
+        while let Some(c) = chars.next() {
+            match (c, chars.peek()) {
+                ('{', Some('{')) | ('}', Some('}')) => {
+                    literal.push(c);
+                    chars.next();
+                }
+                ('}', _) => Err(TemplateError::Unmatched(s.to_string()))?,
+                ('{', _) => {
+                    let mut variable = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => variable.push(c),
+                            None => Err(TemplateError::Unclosed(s.to_string()))?,
+                        }
+                    }
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                    }
+                    segments.push(parse_variable(&variable)?);
+                }
+                (c, _) => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+
+        Ok(Template { segments })
+    }
+}
+
+/// Parses a variable given as `name:width|filter|...`.
+fn parse_variable(s: &str) -> Result<Segment, TemplateError> {
+    let mut parts = s.split('|');
+    let head = parts.next().unwrap_or_default();
+    let (name, width) = match head.split_once(':') {
+        Some((name, width)) => {
+            let zeros = width.starts_with('0');
+            let width = width
+                .parse()
+                .map_err(|_| TemplateError::Variable(s.to_string()))?;
+            (name, Some((width, zeros)))
+        }
+        None => (head, None),
+    };
+    let name = name.trim().to_uppercase();
+    if name.is_empty() || name.contains(['{', '=']) {
+        Err(TemplateError::Variable(s.to_string()))?
+    }
+    if !matches!(name.as_str(), "FILENAME" | "DIRNAME") && FlacTags::from_str(&name).is_err() {
+        Err(TemplateError::Unknown(s.to_string()))?
+    }
+
+    Ok(Segment::Variable {
+        name,
+        width,
+        filters: parts.map(Filter::from_str).collect::<Result<_, _>>()?,
+    })
+}
+
+fn lookup(name: &str, meta: &VorbisComment, path: &Path) -> String {
+    let os_str = match name {
+        "FILENAME" => path.file_stem(),
+        "DIRNAME" => path.parent().and_then(|p| p.file_name()),
+        _ => None,
+    };
+    match os_str {
+        Some(s) => s.to_string_lossy().into_owned(),
+        None => meta
+            .get(name)
+            .and_then(|v| v.first())
+            .cloned()
+            .unwrap_or_default(),
+    }
+}
+
+/// Pads a value on the left to a minimum width. When padding with zeros, integer values (such as
+/// `3/12`) are read as just their number.
+fn pad(value: String, width: Option<(usize, bool)>) -> String {
+    match width {
+        Some((width, true)) => match coerce_integer(&value) {
+            Some(i) => format!("{i:0width$}"),
+            None => format!("{value:0>width$}"),
+        },
+        Some((width, false)) => format!("{value:>width$}"),
+        None => value,
+    }
+}
+
+/// Makes a value safe to use in a file name, replacing path separators, characters reserved on
+/// common filesystems and control characters with `_`, and removing surrounding whitespace and
+/// trailing dots.
+fn sanitize(value: String) -> String {
+    value
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect::<String>()
+        .trim()
+        .trim_end_matches('.')
+        .trim_end()
+        .to_string()
+}
+
+/// A pattern for reading tags from a file's path, such as `%artist%/%album%/%tracknumber% %title%`.
+///
+/// Each `%tag%` matches part of a single path component, and literal percent signs are written as
+/// `%%`. The pattern is matched against the end of the file's path, where the file's extension can
+/// be left out.
+#[derive(Debug, Clone)]
+pub struct PathPattern {
+    regex: Regex,
+    fields: Vec<String>,
+}
+impl PathPattern {
+    /// Reads the tags from a file's canonical path, or `None` if the path doesn't match.
+    pub fn parse(&self, path: &Path) -> Option<Vec<(String, String)>> {
+        let captures = self.regex.captures(path.to_str()?)?;
+
+        Some(
+            self.fields
+                .iter()
+                .zip(captures.iter().skip(1))
+                .filter_map(|(f, c)| Some((f.to_owned(), c?.as_str().trim().to_string())))
+                .filter(|(_, v)| !v.is_empty())
+                .collect(),
+        )
+    }
+}
+impl PartialEq for PathPattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.regex.as_str() == other.regex.as_str()
+    }
+}
+impl Eq for PathPattern {}
+impl FromStr for PathPattern {
+    type Err = TemplateError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut pattern = String::from("(?:^|/)");
+        let mut fields = Vec::new();
+        let mut parts = s.split('%');
+        pattern.push_str(&regex::escape(parts.next().unwrap_or_default()));
+        loop {
+            match (parts.next(), parts.next()) {
+                (Some(""), Some(literal)) => {
+                    pattern.push('%');
+                    pattern.push_str(&regex::escape(literal));
+                }
+                (Some(field), Some(literal)) => {
+                    let field = field.to_uppercase();
+                    validate_field_name(&field).map_err(TemplateError::Field)?;
+                    fields.push(field);
+                    pattern.push_str("([^/]+?)");
+                    pattern.push_str(&regex::escape(literal));
+                }
+                (Some(_), None) => Err(TemplateError::UnclosedPattern(s.to_string()))?,
+                (None, _) => break,
+            }
+        }
+        pattern.push_str(r"(?:\.[^./]+)?$");
+
+        Ok(PathPattern {
+            regex: Regex::new(&pattern).expect("escaped pattern is a valid regex"),
+            fields,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn render(s: &str) -> String {
+        let mut meta = VorbisComment::new();
+        meta.set("TITLE", vec!["Feather"]);
+        meta.set("ARTIST", vec![" Nujabes ", "Cise Starr"]);
+        meta.set("TRACKNUMBER", vec!["3/14"]);
+        Template::from_str(s)
+            .unwrap()
+            .render(&meta, Path::new("Modal Soul/03 Feather.flac"))
+    }
+
+    #[test]
+    fn test_render_template() {
+        assert_eq!(render("{title} (Live)"), "Feather (Live)");
+        assert_eq!(render("{Title}"), "Feather");
+        assert_eq!(render("{tracknumber:02}. {title}"), "03. Feather");
+        assert_eq!(render("{tracknumber:5}"), " 3/14");
+        assert_eq!(render("{artist|trim|upper}"), "NUJABES");
+        assert_eq!(render("{date}"), "");
+        assert_eq!(render("{dirname}/{filename}"), "Modal Soul/03 Feather");
+        assert_eq!(render("{{title}} {{{title}}}"), "{title} {Feather}");
+        assert_eq!(render("No variables"), "No variables");
+    }
+
+    #[test]
+    fn test_render_path() {
+        let mut meta = VorbisComment::new();
+        meta.set("ALBUMARTIST", vec!["AC/DC"]);
+        meta.set("ALBUM", vec!["Who Made Who?"]);
+        meta.set("TITLE", vec!["D.T.  "]);
+        meta.set("TRACKNUMBER", vec!["4"]);
+        let path = Path::new("04.flac");
+        let render = |s: &str| Template::from_str(s).unwrap().render_path(&meta, path);
+
+        assert_eq!(
+            render("{albumartist}/{album}/{tracknumber:02} {title}.flac"),
+            Some(PathBuf::from("AC_DC/Who Made Who_/04 D.T.flac"))
+        );
+        assert_eq!(
+            render("/music/{album}"),
+            Some(PathBuf::from("/music/Who Made Who_"))
+        );
+        assert_eq!(render("{date}/{title}"), None);
+        assert_eq!(render("{date} {title}"), Some(PathBuf::from("D.T")));
+        assert_eq!(render("{album}/{date}"), None);
+    }
+
+    #[test]
+    fn test_parse_template_errors() {
+        assert!(matches!(
+            Template::from_str("{title"),
+            Err(TemplateError::Unclosed(_))
+        ));
+        assert!(matches!(
+            Template::from_str("title}"),
+            Err(TemplateError::Unmatched(_))
+        ));
+        assert!(matches!(
+            Template::from_str("{}"),
+            Err(TemplateError::Variable(_))
+        ));
+        assert!(matches!(
+            Template::from_str("{tracknumber:two}"),
+            Err(TemplateError::Variable(_))
+        ));
+        assert!(matches!(
+            Template::from_str("{title|reverse}"),
+            Err(TemplateError::Filter(_))
+        ));
+        assert!(matches!(
+            Template::from_str("{title} {title"),
+            Err(TemplateError::Unclosed(_))
+        ));
+        assert!(matches!(
+            Template::from_str("{titel}"),
+            Err(TemplateError::Unknown(_))
+        ));
+    }
+
+    #[test]
+    fn test_path_pattern() {
+        let pattern =
+            PathPattern::from_str("%artist%/%date% - %album%/%tracknumber% %title%.flac").unwrap();
+        let expected = [
+            ("ARTIST", "Nujabes"),
+            ("DATE", "2005"),
+            ("ALBUM", "Modal Soul"),
+            ("TRACKNUMBER", "03"),
+            ("TITLE", "Feather"),
+        ]
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .to_vec();
+
+        let path = Path::new("Music/Nujabes/2005 - Modal Soul/03 Feather.flac");
+        assert_eq!(pattern.parse(path), Some(expected.clone()));
+        assert_eq!(pattern.parse(Path::new("Nujabes/03 Feather.flac")), None);
+
+        let pattern = PathPattern::from_str("%artist%/%date% - %album%/%tracknumber% %title%");
+        assert_eq!(pattern.unwrap().parse(path), Some(expected));
+
+        let pattern = PathPattern::from_str("100%% %title%").unwrap();
+        assert_eq!(
+            pattern.parse(Path::new("100% Feather.flac")),
+            Some(vec![("TITLE".to_string(), "Feather".to_string())])
+        );
+        assert!(matches!(
+            PathPattern::from_str("%art=ist%/%title%"),
+            Err(TemplateError::Field(_))
+        ));
+        assert!(matches!(
+            PathPattern::from_str("%artist%/%title"),
+            Err(TemplateError::UnclosedPattern(_))
+        ));
+    }
+}
@@ -0,0 +1,136 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::path::Path;
+use thiserror::Error;
+
+lazy_static! {
+    /// Track position at the start of a line, e.g. `1.`, `01 -`, `3)`, `A1`, or `B.`
+    static ref POSITION: Regex =
+        Regex::new(r"^\s*(?:[A-Za-z]?\d{1,3}(?:\s*[.):-]|\s)|[A-Za-z][.)])\s*").unwrap();
+    /// Track length at the end of a line, e.g. `3:45`, `(3:45)`, or `[1:02:03]`
+    static ref LENGTH: Regex =
+        Regex::new(r"\s*[(\[]?\d{1,2}:\d{2}(?::\d{2})?[)\]]?\s*$").unwrap();
+    /// Separator between a track's artist and title
+    static ref ARTIST: Regex = Regex::new(r"\s+[-–—]\s+").unwrap();
+}
+
+#[derive(Error, Debug)]
+pub enum TracklistError {
+    #[error("Could not read tracklist: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Tracklist {0} has {1} entries, but {2} files matched")]
+    Count(String, usize, usize),
+}
+
+/// Reads the non-empty lines of a tracklist, checking there is one line per file.
+pub fn read_tracklist(path: &Path, files: usize) -> Result<Vec<String>, TracklistError> {
+    let lines = std::fs::read_to_string(path)?
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    match lines.len() == files {
+        true => Ok(lines),
+        false => Err(TracklistError::Count(
+            path.to_string_lossy().into_owned(),
+            lines.len(),
+            files,
+        )),
+    }
+}
+
+/// Removes the track positions and lengths from the lines of a tracklist, e.g. `1. Feather (2:55)`
+/// is read as `Feather`. Positions are only removed when every line has one, so titles such as
+/// `99 Problems` are kept as they are.
+pub fn parse_values(lines: &[String]) -> Vec<String> {
+    let numbered = lines.iter().all(|l| POSITION.is_match(l));
+    lines
+        .iter()
+        .map(|line| {
+            let line = match numbered {
+                true => POSITION.replace(line, ""),
+                false => line.into(),
+            };
+            LENGTH.replace(&line, "").trim().to_string()
+        })
+        .collect()
+}
+
+/// Reads the artist and title from a tracklist value, where the artist is given before a dash,
+/// e.g. `Nujabes – Feather`.
+pub fn split_artist(value: &str) -> (Option<String>, String) {
+    match ARTIST.splitn(value, 2).collect::<Vec<_>>()[..] {
+        [artist, title] if !artist.is_empty() && !title.is_empty() => {
+            (Some(artist.to_string()), title.to_string())
+        }
+        _ => (None, value.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn values(lines: &[&str]) -> Vec<String> {
+        parse_values(&lines.iter().map(|l| l.to_string()).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn test_parse_values() {
+        assert_eq!(
+            values(&[
+                "1. Feather (2:55)",
+                "01 - Feather 2:55",
+                "3) Luv (Sic) Part 3 [1:02:03]",
+                "A1 Feather",
+                "B. Feather",
+                "4. 99 Problems",
+            ]),
+            [
+                "Feather",
+                "Feather",
+                "Luv (Sic) Part 3",
+                "Feather",
+                "Feather",
+                "99 Problems"
+            ]
+        );
+        assert_eq!(
+            values(&[
+                "A Day in the Life",
+                "99 Problems",
+                "7 Rings (2:58)",
+                "B.O.B.",
+                "1-800-273-8255"
+            ]),
+            [
+                "A Day in the Life",
+                "99 Problems",
+                "7 Rings",
+                "B.O.B.",
+                "1-800-273-8255"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_split_artist() {
+        assert_eq!(
+            split_artist("Nujabes – Feather"),
+            (Some("Nujabes".to_string()), "Feather".to_string())
+        );
+        assert_eq!(
+            split_artist("Nujabes feat. Cise Starr - Luv (Sic) Part 3"),
+            (
+                Some("Nujabes feat. Cise Starr".to_string()),
+                "Luv (Sic) Part 3".to_string()
+            )
+        );
+        assert_eq!(split_artist("Feather"), (None, "Feather".to_string()));
+        assert_eq!(
+            split_artist("Spiral-Shaped"),
+            (None, "Spiral-Shaped".to_string())
+        );
+    }
+}